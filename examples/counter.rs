@@ -0,0 +1,101 @@
+//! A file type of its own, served without the rest of the challenge: no built in modules,
+//! packs or control socket. Every read of `Counter/Clicker` counts up.
+//!
+//!     cargo run --example counter -- /tmp/counter
+
+use std::{env, ffi::{c_int, OsStr, OsString}, path::PathBuf, process::exit, time::SystemTime};
+
+use fuser::{consts::FOPEN_DIRECT_IO, FileAttr, FileType};
+use hack::{add_file, get_unique_ino, read, register, set_skip_intro, File, Module, DEFAULT_MODE, GID, PERMISSION_DENIED, UID};
+
+#[derive(Debug)]
+struct Clicker {
+    attr: FileAttr,
+    name: OsString,
+    clicks: u64,
+    data: Vec<u8>,
+}
+
+impl Clicker {
+    fn new() -> Self {
+        Self {
+            attr: FileAttr {
+                ino: get_unique_ino(),
+                size: 0,
+                blocks: 0,
+                atime: SystemTime::now(),
+                mtime: SystemTime::now(),
+                ctime: SystemTime::now(),
+                crtime: SystemTime::now(),
+                kind: FileType::RegularFile,
+                perm: DEFAULT_MODE as u16,
+                nlink: 0,
+                uid: *UID,
+                gid: *GID,
+                rdev: 0,
+                blksize: 0,
+                flags: 0,
+            },
+            name: OsString::from("Clicker"),
+            clicks: 0,
+            data: Vec::new(),
+        }
+    }
+}
+
+impl File for Clicker {
+    fn name(&self) -> &OsStr {
+        &self.name
+    }
+
+    fn attr(&self) -> &FileAttr {
+        &self.attr
+    }
+
+    fn setattr(&mut self, _mode: Option<u32>, _uid: Option<u32>, _gid: Option<u32>, _size: Option<u64>, _fh: Option<u64>, _flags: Option<u32>) -> Result<(), c_int> {
+        Err(PERMISSION_DENIED)
+    }
+
+    fn rename(&mut self, _new_name: &OsStr, _in_user_dir: bool) -> Result<(), c_int> {
+        Err(PERMISSION_DENIED)
+    }
+
+    fn read(&mut self, offset: i64, size: u32, _flags: i32) -> Result<&[u8], c_int> {
+        if offset == 0 {
+            self.clicks += 1;
+            self.data = format!("Clicked {} times\n", self.clicks).into_bytes();
+            self.attr.size = self.data.len() as u64;
+        }
+        read(&self.data, offset, size)
+    }
+
+    fn write(&mut self, _offset: i64, _data: &[u8], _write_flags: u32, _flags: i32) -> Result<u32, c_int> {
+        Err(PERMISSION_DENIED)
+    }
+
+    fn delete(&mut self) -> Result<(), c_int> {
+        Err(PERMISSION_DENIED)
+    }
+
+    // The size changes with every read, so the kernel must not cache it.
+    fn open(&mut self, _flags: i32) -> Result<u32, c_int> {
+        Ok(FOPEN_DIRECT_IO)
+    }
+}
+
+fn main() {
+    let Some(mount_point) = env::args_os().nth(1).map(PathBuf::from) else {
+        eprintln!("usage: counter <mount point>");
+        exit(2);
+    };
+    register(Module::new("counter", "Counter", || {
+        add_file("Counter", Box::new(Clicker::new()));
+    }));
+    set_skip_intro(true);
+
+    // Only for the user running it, allow_other needs user_allow_other in /etc/fuse.conf.
+    if let Err(e) = hack::mount(&mount_point, &["noatime".to_string()]) {
+        eprintln!("Failed to mount on {}: {e}", mount_point.display());
+        exit(1);
+    }
+}
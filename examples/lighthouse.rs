@@ -0,0 +1,46 @@
+//! A room made from the builder helpers, with a hint and a `Victory` file, served alone like
+//! `counter`:
+//!
+//!     cargo run --example lighthouse -- /tmp/lighthouse
+
+use std::{env, path::PathBuf, process::exit};
+
+use hack::{add_file, get_unique_ino, register, set_skip_intro, str_to_vec, text_file, Hint, Module, TriggerFile, DEFAULT_MODE};
+
+const ID: &str = "lighthouse";
+const BASE_PATH: &str = "Lighthouse";
+
+const KEEPER_MESSAGE: &str =
+"The lamp went out and I can't find the switch.
+I know it is somewhere up the stairs, but I never see them.
+";
+
+const SWITCH_MESSAGE: &str =
+"Click.
+The light is back on.
+";
+
+fn start() {
+    add_file(BASE_PATH, text_file("Keeper", KEEPER_MESSAGE));
+    // Missing directories are made on the way.
+    add_file(&format!("{BASE_PATH}/.stairs"), Box::new(TriggerFile::new(victory, "Switch", str_to_vec(SWITCH_MESSAGE), get_unique_ino(), DEFAULT_MODE, 0)));
+}
+
+fn victory() {
+    hack::victory(ID);
+}
+
+fn main() {
+    let Some(mount_point) = env::args_os().nth(1).map(PathBuf::from) else {
+        eprintln!("usage: lighthouse <mount point>");
+        exit(2);
+    };
+    let hints = vec![Hint::new("Some directories only show up with ls -a.", 180, 3)];
+    register(Module::new(ID, BASE_PATH, start).hints(hints));
+    set_skip_intro(true);
+
+    if let Err(e) = hack::mount(&mount_point, &["noatime".to_string()]) {
+        eprintln!("Failed to mount on {}: {e}", mount_point.display());
+        exit(1);
+    }
+}
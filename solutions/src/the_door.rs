@@ -1,8 +1,12 @@
 use std::fs::File;
 
 fn main() {
+    // The door says how many it wants, it is different for everyone.
+    let text = std::fs::read_to_string("./Heavy_Door").unwrap();
+    let needed: usize = text.split("need ").nth(1).and_then(|x| x.split_whitespace().next()).and_then(|x| x.parse().ok()).unwrap_or(10);
+
     let mut files = Vec::new();
-    for _ in 0..10 {
+    for _ in 0..needed {
         files.push(File::open("./Heavy_Door"));
     }
 
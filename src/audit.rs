@@ -0,0 +1,140 @@
+use std::{ffi::c_int, fs::{self, OpenOptions}, io::{LineWriter, Write}, path::Path, sync::Mutex, time::{SystemTime, UNIX_EPOCH}};
+
+use lazy_static::lazy_static;
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::{main_fs::current_request, random};
+
+lazy_static! {
+    static ref AUDIT_LOG: Mutex<Option<LineWriter<fs::File>>> = {
+        Mutex::new(None)
+    };
+}
+
+/// One filesystem operation, written as a single line of JSON. Fields that do not apply
+/// to the operation are left out.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Record {
+    /// Milliseconds since the unix epoch.
+    pub time: u64,
+    /// `lookup`, `getattr`, `setattr`, `mknod`, `mkdir`, `unlink`, `rmdir`, `rename`,
+    /// `open`, `read`, `write`, `release` or `readdir`. `session` starts every mount.
+    pub op: String,
+    /// Path as the player sees it, from the root of the mount.
+    pub path: String,
+    pub uid: u32,
+    pub gid: u32,
+    pub pid: u32,
+    /// Name of the calling process from `/proc/<pid>/comm`.
+    pub process: String,
+    /// 0 on success, otherwise the errno sent back.
+    pub errno: c_int,
+    /// Target of a rename.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub new_path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset: Option<i64>,
+    /// Bytes asked for by a read.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u32>,
+    /// Bytes read or written.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub len: Option<u64>,
+    /// Open flags, or the flags of a read or write.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flags: Option<i32>,
+    /// Mode of mknod, mkdir and setattr.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<u32>,
+    /// New size of a setattr.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub set_size: Option<u64>,
+    /// Write flags the kernel passed along with a write, like `FUSE_WRITE_CACHE`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub write_flags: Option<u32>,
+    /// What was written, byte for byte.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Vec<u8>>,
+    /// Seed of the puzzles, on `session` records.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+}
+
+impl Record {
+    /// A record for the request being handled.
+    pub fn new<T>(op: &str, path: Option<String>, result: &Result<T, c_int>) -> Record {
+        let request = current_request();
+        let pid = request.map(|req| req.pid).unwrap_or(0);
+        Record {
+            time: SystemTime::now().duration_since(UNIX_EPOCH).map(|x| x.as_millis() as u64).unwrap_or(0),
+            op: op.to_string(),
+            path: path.unwrap_or_else(|| "?".to_string()),
+            uid: request.map(|req| req.uid).unwrap_or(0),
+            gid: request.map(|req| req.gid).unwrap_or(0),
+            pid,
+            process: process_name(pid),
+            errno: result.as_ref().err().copied().unwrap_or(0),
+            ..Record::default()
+        }
+    }
+}
+
+fn process_name(pid: u32) -> String {
+    fs::read_to_string(format!("/proc/{pid}/comm"))
+        .map(|name| name.trim_end().to_string())
+        .unwrap_or_default()
+}
+
+/// Starts appending every operation to `path`.
+pub fn open(path: &Path) -> std::io::Result<()> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    *AUDIT_LOG.lock().unwrap() = Some(LineWriter::new(file));
+    Ok(())
+}
+
+/// Marks the start of a mount with the seed its puzzles come from, so `replay` can draw
+/// the same ones. Call it once the save file is loaded.
+pub fn session() {
+    if enabled() {
+        record(Record { path: "/".to_string(), seed: Some(random::seed()), ..Record::new::<()>("session", None, &Ok(())) });
+    }
+}
+
+/// The seed of the first session in `records`.
+pub fn seed(records: &[Record]) -> Option<u64> {
+    records.iter().find(|record| record.op == "session").and_then(|record| record.seed)
+}
+
+pub fn enabled() -> bool {
+    AUDIT_LOG.lock().unwrap().is_some()
+}
+
+pub fn record(record: Record) {
+    let mut log = AUDIT_LOG.lock().unwrap();
+    let Some(log) = log.as_mut() else {
+        return;
+    };
+    let line = serde_json::to_string(&record).unwrap();
+    if let Err(e) = writeln!(log, "{line}") {
+        warn!("failed to write the audit log: {e}");
+    }
+}
+
+/// Flushes and closes the log, nothing is recorded after this.
+pub fn close() {
+    if let Some(mut log) = AUDIT_LOG.lock().unwrap().take() {
+        if let Err(e) = log.flush() {
+            warn!("failed to write the audit log: {e}");
+        }
+    }
+}
+
+pub fn read(path: &Path) -> Result<Vec<Record>, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| serde_json::from_str(line).map_err(|e| format!("{}:{}: {e}", path.display(), i + 1)))
+        .collect()
+}
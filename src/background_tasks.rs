@@ -1,82 +1,384 @@
-use std::{ffi::OsStr, path::{Component, Path, PathBuf}, str::FromStr, thread};
+use std::{cell::RefCell, ffi::OsStr, path::{Component, Path}, sync::{mpsc::{self, Receiver, Sender}, Mutex}, thread};
 
-use crate::{dirs::Dir, files::File, main_fs::{get_unique_ino, lookup, FsData, Ino, DATA, GID, UID}, user_files::NormalDir};
+use fuser::FileType;
+use lazy_static::lazy_static;
+use log::{trace, warn};
+
+use crate::{dirs::Dir, files::File, main_fs::{current_player, get_unique_ino, invalidate, lookup, with_player, FsData, Ino, DATA, GID, SHARED_PLAYER, UID}, user_files::NormalDir};
 
 pub const DEFAULT_MODE: u32 = 0o777;
 
-pub fn add_file(path: &str, file: Box<dyn File>) {
-    let path = path.to_string();
-    println!("Adding-------------------");
-    thread::spawn(move || {
-        _add_file(&path, file);
+// Every change to the tree goes through one queue and is applied by one worker thread, in
+// the order it was queued. Paths are relative to the root of the player whose request (or
+// trigger) queued the change.
+
+lazy_static! {
+    static ref QUEUE: Mutex<Sender<Task>> = {
+        let (sender, receiver) = mpsc::channel();
+        thread::Builder::new()
+            .name("tasks".to_string())
+            .spawn(move || worker(receiver))
+            .expect("Failed to start the task worker");
+        Mutex::new(sender)
+    };
+}
+
+thread_local! {
+    static BATCH: RefCell<Option<Task>> = const { RefCell::new(None) };
+}
+
+#[derive(Debug)]
+enum Op {
+    AddFile(String, Box<dyn File>),
+    AddDir(String, u32),
+    RmFile(String),
+    RmDir(String),
+    Rm(String),
+    LinkDir(String, u32),
+    ReplaceText(String, Vec<u8>, Vec<u8>),
+}
+
+/// Changes that are applied together under one lock of `DATA`, or not at all.
+#[derive(Debug, Default)]
+struct Task {
+    ops: Vec<(u32, Op)>,
+    waiting: Vec<Sender<Result<(), String>>>,
+}
+
+/// What to do to take an applied change back when a later one in the same task fails.
+enum Undo {
+    Linked { parent: u64, ino: u64 },
+    Created(u64),
+    /// `index` is where the child was, so a rollback puts it back in the same place.
+    Unlinked { parent: u64, ino: u64, index: usize },
+    Dropped(u64, Ino),
+    Text { ino: u64, old: Vec<u8>, new: Vec<u8> },
+}
+
+/// A queued change. Dropping it is fine, failures are logged either way.
+#[derive(Debug)]
+pub struct Pending(Receiver<Result<(), String>>);
+
+impl Pending {
+    /// Blocks until the change is applied. Never wait while holding `DATA`, or inside a
+    /// `batch`, the worker needs the lock and the batch is only queued at its end.
+    pub fn wait(self) -> Result<(), String> {
+        self.0.recv().unwrap_or_else(|_| Err("the task worker is gone".to_string()))
+    }
+}
+
+pub fn add_file(path: &str, file: Box<dyn File>) -> Pending {
+    trace!(path = path, player = current_player(); "queued add");
+    queue(Op::AddFile(path.to_string(), file))
+}
+
+pub fn add_dir(path: &str, mode: u32) -> Pending {
+    queue(Op::AddDir(path.to_string(), mode))
+}
+
+/// Fails if the file is not there.
+pub fn rm_file(path: &str) -> Pending {
+    trace!(path = path, player = current_player(); "queued remove");
+    queue(Op::RmFile(path.to_string()))
+}
+
+/// Removes a directory and everything below it. Missing directories are ignored.
+pub fn rm_dir(path: &str) -> Pending {
+    queue(Op::RmDir(path.to_string()))
+}
+
+/// Removes a file or a directory with everything below it from the current player's tree
+/// only. A shared directory is just unlinked from their root, paths inside one are refused
+/// since every player sees them. Fails if nothing is there.
+pub fn rm(path: &str) -> Pending {
+    queue(Op::Rm(path.to_string()))
+}
+
+/// Swaps `old` for `new` in every file called like the last part of `path` that still holds
+/// `old`, see `File::replace_text`. Other files are left alone.
+pub fn replace_text(path: &str, old: &str, new: &str) -> Pending {
+    queue(Op::ReplaceText(path.to_string(), old.as_bytes().to_vec(), new.as_bytes().to_vec()))
+}
+
+/// Makes a directory from the shared tree show up in the player's root as well.
+pub fn link_shared_dir(name: &str, shared_player: u32) -> Pending {
+    queue(Op::LinkDir(name.to_string(), shared_player))
+}
+
+/// Queues everything `f` adds or removes as a single task, so players see all of it or none
+/// of it. A batch inside a batch joins the outer one.
+pub fn batch(f: impl FnOnce()) -> Pending {
+    let (sender, receiver) = mpsc::channel();
+    let nested = BATCH.with(|batch| {
+        let mut batch = batch.borrow_mut();
+        let nested = batch.is_some();
+        batch.get_or_insert_with(Task::default).waiting.push(sender);
+        nested
     });
+    if nested {
+        f();
+        return Pending(receiver);
+    }
+
+    // Queue what was collected even if `f` panics, so the batch does not swallow later changes.
+    struct Commit;
+    impl Drop for Commit {
+        fn drop(&mut self) {
+            if let Some(task) = BATCH.with(|batch| batch.borrow_mut().take()) {
+                send(task);
+            }
+        }
+    }
+    let _commit = Commit;
+    f();
+    Pending(receiver)
 }
 
-pub fn rm_file(path: &str) {
-    let path = path.to_string();
-    println!("Removing-------------------");
-    thread::spawn(move || {
-        _rm_file(&path);
+/// Waits for everything queued so far.
+pub fn flush() {
+    let _ = batch(|| {}).wait();
+}
+
+fn queue(op: Op) -> Pending {
+    let (sender, receiver) = mpsc::channel();
+    let op = (current_player(), op);
+    BATCH.with(|batch| match batch.borrow_mut().as_mut() {
+        Some(task) => {
+            task.ops.push(op);
+            task.waiting.push(sender);
+        }
+        None => send(Task { ops: vec![op], waiting: vec![sender] }),
     });
+    Pending(receiver)
 }
 
-pub fn _rm_file(path: &str) {
+fn send(task: Task) {
+    QUEUE.lock().unwrap().send(task).expect("The task worker is gone");
+}
+
+fn worker(receiver: Receiver<Task>) {
+    for task in receiver {
+        let result = apply(task.ops).map(invalidate);
+        if let Err(e) = &result {
+            warn!("background task failed: {e}");
+        }
+        for sender in task.waiting {
+            let _ = sender.send(result.clone());
+        }
+    }
+}
+
+/// Returns the files whose text changed, the kernel may have cached the old one.
+fn apply(ops: Vec<(u32, Op)>) -> Result<Vec<u64>, String> {
     let mut data = DATA.lock().unwrap();
-    let path = PathBuf::from_str(path).unwrap();
-    let filename = path.file_name().unwrap();
-    let path = path.parent().unwrap();
-    let mut parent = 1;
+    let mut undo = Vec::new();
+    for (player, op) in ops {
+        if let Err(e) = with_player(player, || apply_op(op, &mut data, &mut undo)) {
+            rollback(undo, &mut data);
+            return Err(e);
+        }
+    }
+    Ok(undo.iter().filter_map(|step| match step {
+        Undo::Text { ino, .. } => Some(*ino),
+        _ => None,
+    }).collect())
+}
 
+fn apply_op(op: Op, data: &mut FsData, undo: &mut Vec<Undo>) -> Result<(), String> {
+    match op {
+        Op::AddFile(path, file) => {
+            let parent = walk(Path::new(&path), Some(DEFAULT_MODE), data, undo)?;
+            let ino = file.attr().ino;
+            data.inos.insert(ino, Ino::File(file));
+            undo.push(Undo::Created(ino));
+            link(parent, ino, data, undo)?;
+        }
+        Op::AddDir(path, mode) => {
+            walk(Path::new(&path), Some(mode), data, undo)?;
+        }
+        Op::RmFile(path) => {
+            let path = Path::new(&path);
+            let name = path.file_name().ok_or_else(|| format!("can not remove {}", path.display()))?;
+            let parent = walk(path.parent().unwrap_or(Path::new("")), None, data, undo)?;
+            let ino = data.inos.get(&parent).unwrap().unwrap_dir().lookup_child(name, &data.inos)
+                .map_err(|_| format!("{} does not exist", path.display()))?;
+            unlink(parent, ino, data, undo)?;
+            drop_ino(ino, data, undo);
+        }
+        Op::Rm(path) => {
+            let path = Path::new(&path);
+            let name = path.file_name().ok_or_else(|| format!("can not remove {}", path.display()))?;
+            let parent = walk(path.parent().unwrap_or(Path::new("")), None, data, undo)?;
+            let ino = data.inos.get(&parent).unwrap().unwrap_dir().lookup_child(name, &data.inos)
+                .map_err(|_| format!("{} does not exist", path.display()))?;
+
+            let root = data.root(current_player());
+            // Shared directories are linked into every player's root, see `LinkDir`.
+            let first = path.components().find_map(|x| match x {
+                Component::Normal(name) => Some(name),
+                _ => None,
+            });
+            let shared = match (first, data.roots.get(&SHARED_PLAYER).copied()) {
+                (Some(first), Some(shared_root)) => matches!((lookup(root, first, data), lookup(shared_root, first, data)), (Ok(mine), Ok(theirs)) if mine.ino == theirs.ino),
+                _ => false,
+            };
+            if shared && parent != root {
+                return Err(format!("{} is in a shared directory, every player would lose it", path.display()));
+            }
+
+            unlink(parent, ino, data, undo)?;
+            if !shared {
+                let mut to_remove = vec![ino];
+                while let Some(ino) = to_remove.pop() {
+                    if let Some(Ino::Dir(dir)) = data.inos.get(&ino) {
+                        to_remove.extend((0..).map_while(|i| dir.get_child(i)));
+                    }
+                    drop_ino(ino, data, undo);
+                }
+            }
+        }
+        Op::RmDir(path) => {
+            let path = Path::new(&path);
+            let Some(name) = path.file_name() else {
+                return Ok(());
+            };
+            let Ok(parent) = walk(path.parent().unwrap_or(Path::new("")), None, data, undo) else {
+                return Ok(());
+            };
+            let Ok(ino) = data.inos.get(&parent).unwrap().unwrap_dir().lookup_child(name, &data.inos) else {
+                return Ok(());
+            };
+
+            // Shared directories are linked into every player's root.
+            let mut parents: Vec<u64> = data.roots.values().copied().collect();
+            if !parents.contains(&parent) {
+                parents.push(parent);
+            }
+            for parent in parents {
+                if lookup(parent, name, data).is_ok_and(|attr| attr.ino == ino) {
+                    unlink(parent, ino, data, undo)?;
+                }
+            }
+
+            let mut to_remove = vec![ino];
+            while let Some(ino) = to_remove.pop() {
+                if let Some(Ino::Dir(dir)) = data.inos.get(&ino) {
+                    to_remove.extend((0..).map_while(|i| dir.get_child(i)));
+                }
+                drop_ino(ino, data, undo);
+            }
+        }
+        Op::LinkDir(name, from) => {
+            let name = OsStr::new(&name);
+            let from_root = data.root(from);
+            let to_root = data.root(current_player());
+            let ino = match lookup(from_root, name, data) {
+                Ok(attr) => attr.ino,
+                Err(_) => add_one_dir(from_root, default_dir(name, DEFAULT_MODE), data, undo)?,
+            };
+            if lookup(to_root, name, data).is_err() {
+                link(to_root, ino, data, undo)?;
+            }
+        }
+        Op::ReplaceText(path, old, new) => {
+            let path = Path::new(&path);
+            let name = path.file_name().ok_or_else(|| format!("can not change {}", path.display()))?;
+            let Ok(parent) = walk(path.parent().unwrap_or(Path::new("")), None, data, undo) else {
+                return Ok(());
+            };
+            let children: Vec<u64> = (0..).map_while(|i| data.inos.get(&parent).unwrap().unwrap_dir().get_child(i)).collect();
+            for ino in children {
+                if let Some(Ino::File(file)) = data.inos.get_mut(&ino) {
+                    if file.name() == name && file.replace_text(&old, &new) {
+                        undo.push(Undo::Text { ino, old: old.clone(), new: new.clone() });
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Finds the directory at `path` from the current player's root. Missing directories are
+/// made with `create` as their mode, or are an error without it.
+fn walk(path: &Path, create: Option<u32>, data: &mut FsData, undo: &mut Vec<Undo>) -> Result<u64, String> {
+    let mut parent = data.root(current_player());
     for component in path.components() {
         let Component::Normal(next) = component else {
             continue;
         };
 
-        if let Ok(next_attr) = lookup(parent, next, &data) {
-            parent = next_attr.ino;
-        } else {
-            panic!("Given rm target path that does not exist");
-        }
+        parent = match (lookup(parent, next, data).copied(), create) {
+            (Ok(attr), _) if attr.kind == FileType::Directory => attr.ino,
+            (Ok(_), _) => return Err(format!("{} is not a directory", path.display())),
+            (Err(_), Some(mode)) => add_one_dir(parent, default_dir(next, mode), data, undo)?,
+            (Err(_), None) => return Err(format!("{} does not exist", path.display())),
+        };
     }
+    Ok(parent)
+}
 
-    let file_ino = data.inos.get(&parent).unwrap().unwrap_dir().lookup_child(filename, &data.inos).unwrap();
-    data.inos.get_mut(&parent).unwrap().unwrap_dir_mut().remove_child(file_ino).unwrap();
-    data.inos.remove(&file_ino);
+fn dir_mut(ino: u64, data: &mut FsData) -> Result<&mut Box<dyn Dir>, String> {
+    data.inos.get_mut(&ino).and_then(Ino::try_unwrap_dir_mut).ok_or_else(|| format!("ino {ino} is not a directory"))
 }
 
-fn _add_file(path: &str, file: Box<dyn File>) {
-    let mut data = DATA.lock().unwrap();
-    let path = PathBuf::from_str(path).unwrap();
-    let mut parent = 1;
+fn link(parent: u64, ino: u64, data: &mut FsData, undo: &mut Vec<Undo>) -> Result<(), String> {
+    dir_mut(parent, data)?.add_child(ino).map_err(|e| format!("can not add ino {ino} to {parent}: errno {e}"))?;
+    undo.push(Undo::Linked { parent, ino });
+    Ok(())
+}
 
-    for component in path.components() {
-        let Component::Normal(next) = component else {
-            continue;
-        };
+fn unlink(parent: u64, ino: u64, data: &mut FsData, undo: &mut Vec<Undo>) -> Result<(), String> {
+    let dir = dir_mut(parent, data)?;
+    let index = (0..).map_while(|i| dir.get_child(i)).position(|child| child == ino)
+        .ok_or_else(|| format!("ino {ino} is not in {parent}"))?;
+    dir.remove_child(ino).map_err(|e| format!("can not remove ino {ino} from {parent}: errno {e}"))?;
+    undo.push(Undo::Unlinked { parent, ino, index });
+    Ok(())
+}
 
-        if let Ok(next_attr) = lookup(parent, next, &data) {
-            parent = next_attr.ino;
-        } else {
-            parent = _add_one_dir(parent, default_dir(next), &mut data);
+fn drop_ino(ino: u64, data: &mut FsData, undo: &mut Vec<Undo>) {
+    if let Some(removed) = data.inos.remove(&ino) {
+        undo.push(Undo::Dropped(ino, removed));
+    }
+}
+
+fn rollback(undo: Vec<Undo>, data: &mut FsData) {
+    for step in undo.into_iter().rev() {
+        match step {
+            Undo::Linked { parent, ino } => {
+                if dir_mut(parent, data).and_then(|dir| dir.remove_child(ino).map_err(|e| format!("errno {e}"))).is_err() {
+                    warn!(ino = ino; "rollback left a file behind");
+                }
+            }
+            Undo::Created(ino) => {
+                data.inos.remove(&ino);
+            }
+            Undo::Unlinked { parent, ino, index } => {
+                if dir_mut(parent, data).and_then(|dir| dir.insert_child(index, ino).map_err(|e| format!("errno {e}"))).is_err() {
+                    warn!(ino = ino; "rollback lost a file");
+                }
+            }
+            Undo::Dropped(ino, removed) => {
+                data.inos.insert(ino, removed);
+            }
+            Undo::Text { ino, old, new } => {
+                if let Some(Ino::File(file)) = data.inos.get_mut(&ino) {
+                    file.replace_text(&new, &old);
+                }
+            }
         }
     }
-    let ino = file.attr().ino;
-    data.inos.insert(ino, Ino::File(file));
-    data.inos.get_mut(&parent).unwrap().unwrap_dir_mut().add_child(ino).unwrap();
 }
 
-fn _add_one_dir(parent: u64, dir: Box<dyn Dir>, data: &mut FsData) -> u64 {
+fn add_one_dir(parent: u64, dir: Box<dyn Dir>, data: &mut FsData, undo: &mut Vec<Undo>) -> Result<u64, String> {
     let new_ino = dir.attr().ino;
-    data.inos.get_mut(&parent).unwrap().unwrap_dir_mut().add_child(new_ino).unwrap();
     data.inos.insert(new_ino, Ino::Dir(dir));
-    return new_ino;
+    undo.push(Undo::Created(new_ino));
+    link(parent, new_ino, data, undo)?;
+    Ok(new_ino)
 }
 
-fn default_dir(name: &OsStr) -> Box<dyn Dir> {
-    Box::new(NormalDir::new(name, false, get_unique_ino(), DEFAULT_MODE, *UID, *GID, 0))
+fn default_dir(name: &OsStr, mode: u32) -> Box<dyn Dir> {
+    Box::new(NormalDir::new(name, false, get_unique_ino(), mode, *UID, *GID, 0))
 }
-// fn parent_ino(path: &Path, data: &FsData) -> Option<u64> {
-
-    
-//     Some(parent)
-// }
\ No newline at end of file
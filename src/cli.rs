@@ -0,0 +1,252 @@
+use std::{fs, path::{Path, PathBuf}, process::Command, sync::Mutex};
+
+use clap::{Args, Parser, Subcommand};
+use fuser::MountOption;
+use lazy_static::lazy_static;
+use log::info;
+
+use crate::{messages::MESSAGES_DIR, packs::PACKS_DIR};
+
+pub const DEFAULT_MOUNT_POINT: &str = "./challenge";
+pub const DEFAULT_MOUNT_OPTIONS: [&str; 4] = ["allow_other", "auto_unmount", "exec", "noatime"];
+
+lazy_static! {
+    static ref MOUNT_POINT: Mutex<PathBuf> = {
+        Mutex::new(PathBuf::from(DEFAULT_MOUNT_POINT))
+    };
+}
+
+/// A linux challenge that lives in a FUSE filesystem.
+#[derive(Debug, Parser)]
+#[command(version, subcommand_precedence_over_arg = true)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Cmd>,
+    /// Arguments for `run`, which is what happens without a subcommand.
+    #[command(flatten)]
+    pub run: RunArgs,
+    /// Where saves, flags and the scoreboard are kept [env: HACK_STATE_DIR]
+    #[arg(long, global = true)]
+    pub state_dir: Option<PathBuf>,
+    /// Control socket of the challenge, `control.sock` in the state dir by default [env: HACK_CONTROL_SOCKET]
+    #[arg(long, global = true)]
+    pub control_socket: Option<PathBuf>,
+    /// Seed for puzzle parameters, overrides the one in the save file [env: HACK_SEED]
+    #[arg(long, global = true)]
+    pub seed: Option<u64>,
+    /// Directory with challenge packs.
+    #[arg(long, global = true, default_value = PACKS_DIR)]
+    pub packs: PathBuf,
+    /// Directory with the story text, one `<locale>.toml` per language.
+    #[arg(long, global = true, default_value = MESSAGES_DIR)]
+    pub messages: PathBuf,
+    /// Language of players that did not pick one [env: HACK_LOCALE]
+    #[arg(long, global = true)]
+    pub locale: Option<String>,
+    /// More output, repeat for more.
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+    /// Only print warnings and errors.
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
+    /// Log filter in `RUST_LOG` syntax, e.g. `hack::modules::many_open=trace` [env: RUST_LOG]
+    #[arg(long, global = true)]
+    pub log: Option<String>,
+    /// Append the log to this file instead of printing it.
+    #[arg(long, global = true)]
+    pub log_file: Option<PathBuf>,
+}
+
+impl Cli {
+    pub fn verbosity(&self) -> i8 {
+        if self.quiet { -1 } else { self.verbose as i8 }
+    }
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum Cmd {
+    /// Mount the challenge (the default).
+    Run(RunArgs),
+    /// List the modules that would be built.
+    Modules {
+        #[command(flatten)]
+        select: ModuleSelection,
+    },
+    /// Check flags handed in by players, reads them from stdin if none are given.
+    VerifyFlag {
+        /// Also require the flags to belong to this uid.
+        #[arg(long)]
+        uid: Option<u32>,
+        flags: Vec<String>,
+    },
+    /// Play an audit log back against a fresh filesystem and report where it goes differently.
+    Replay {
+        #[arg(value_name = "LOG")]
+        audit_log: PathBuf,
+        /// Only replay what this uid did.
+        #[arg(long)]
+        uid: Option<u32>,
+        #[command(flatten)]
+        select: ModuleSelection,
+        /// Set this if the recorded challenge ran with `--skip-intro`.
+        #[arg(long)]
+        skip_intro: bool,
+    },
+    /// Send instructor commands to a running challenge, reads them from stdin if none are given.
+    /// `hack control help` lists them.
+    Control {
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        command: Vec<String>,
+    },
+    /// Run the reference solutions against a fresh mount and report which modules they still beat.
+    CheckSolutions {
+        /// The solutions crate.
+        #[arg(default_value = "./solutions")]
+        dir: PathBuf,
+    },
+    /// List story text that a language leaves out and keys no module uses.
+    CheckMessages,
+    /// Unmount a challenge, for example one left behind by a crash.
+    Unmount {
+        #[arg(default_value = DEFAULT_MOUNT_POINT)]
+        mount_point: PathBuf,
+        /// Detach it even if files in it are still open.
+        #[arg(long)]
+        lazy: bool,
+    },
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct RunArgs {
+    #[arg(default_value = DEFAULT_MOUNT_POINT)]
+    pub mount_point: PathBuf,
+    /// Mount options like `ro` or `fsname=hack`, replacing the defaults (allow_other, auto_unmount, exec, noatime).
+    #[arg(short = 'o', long = "option", value_delimiter = ',')]
+    pub options: Vec<String>,
+    #[command(flatten)]
+    pub select: ModuleSelection,
+    /// Start new players in the hub instead of the welcome files.
+    #[arg(long)]
+    pub skip_intro: bool,
+    /// Stay attached to the terminal (the default).
+    #[arg(long, conflicts_with = "daemon")]
+    pub foreground: bool,
+    /// Detach and keep running in the background.
+    #[arg(long)]
+    pub daemon: bool,
+    /// Serve the scoreboard over HTTP on this address, or on this port of localhost [env: HACK_SCOREBOARD_ADDR]
+    #[arg(long)]
+    pub scoreboard_addr: Option<String>,
+    /// Allow a scoreboard address other machines can reach. It has no authentication.
+    #[arg(long)]
+    pub scoreboard_public: bool,
+    /// Append every filesystem operation to this file as JSON lines.
+    #[arg(long)]
+    pub audit_log: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Default, Args)]
+pub struct ModuleSelection {
+    /// Only build these modules, by id or directory name.
+    #[arg(long, value_delimiter = ',')]
+    pub modules: Vec<String>,
+    /// Leave these modules out.
+    #[arg(long, value_delimiter = ',')]
+    pub exclude: Vec<String>,
+    /// `easy`, `normal` or `hard` (or 1 to 3) for every module, or `<module>=<difficulty>` for one.
+    #[arg(long, value_delimiter = ',')]
+    pub difficulty: Vec<String>,
+    /// `<module>=<required module>`: keep the first module locked until the second is solved.
+    #[arg(long, value_delimiter = ',')]
+    pub requires: Vec<String>,
+}
+
+pub fn mount_point() -> PathBuf {
+    MOUNT_POINT.lock().unwrap().clone()
+}
+
+pub fn set_mount_point(path: PathBuf) {
+    *MOUNT_POINT.lock().unwrap() = path;
+}
+
+pub fn mount_options(options: &[String]) -> Vec<MountOption> {
+    if options.is_empty() {
+        return DEFAULT_MOUNT_OPTIONS.iter().map(|x| mount_option(x)).collect();
+    }
+    options.iter().map(|x| mount_option(x)).collect()
+}
+
+fn mount_option(option: &str) -> MountOption {
+    match option.split_once('=') {
+        Some(("fsname", name)) => return MountOption::FSName(name.to_string()),
+        Some(("subtype", name)) => return MountOption::Subtype(name.to_string()),
+        _ => {}
+    }
+    match option {
+        "allow_other" => MountOption::AllowOther,
+        "allow_root" => MountOption::AllowRoot,
+        "auto_unmount" => MountOption::AutoUnmount,
+        "default_permissions" => MountOption::DefaultPermissions,
+        "dev" => MountOption::Dev,
+        "nodev" => MountOption::NoDev,
+        "suid" => MountOption::Suid,
+        "nosuid" => MountOption::NoSuid,
+        "ro" => MountOption::RO,
+        "rw" => MountOption::RW,
+        "exec" => MountOption::Exec,
+        "noexec" => MountOption::NoExec,
+        "atime" => MountOption::Atime,
+        "noatime" => MountOption::NoAtime,
+        "dirsync" => MountOption::DirSync,
+        "sync" => MountOption::Sync,
+        "async" => MountOption::Async,
+        other => MountOption::CUSTOM(other.to_string()),
+    }
+}
+
+/// Forks into the background. Has to happen before any threads are started.
+pub fn daemonize() {
+    unsafe {
+        match libc::fork() {
+            -1 => panic!("Failed to fork: {}", std::io::Error::last_os_error()),
+            0 => {}
+            pid => {
+                info!("running in the background as pid {pid}");
+                std::process::exit(0);
+            }
+        }
+        libc::setsid();
+
+        let null = libc::open(c"/dev/null".as_ptr(), libc::O_RDWR);
+        if null >= 0 {
+            libc::dup2(null, 0);
+            libc::dup2(null, 1);
+            libc::dup2(null, 2);
+        }
+    }
+}
+
+/// Tries the unmount helpers in the order they usually work for a normal user. A `lazy`
+/// unmount detaches the challenge even while files in it are open.
+pub fn unmount(mount_point: &Path, lazy: bool) -> Result<(), String> {
+    let attempts: [(&str, &[&str]); 3] = if lazy {
+        [("fusermount3", &["-u", "-z"]), ("fusermount", &["-u", "-z"]), ("umount", &["-l"])]
+    } else {
+        [("fusermount3", &["-u"]), ("fusermount", &["-u"]), ("umount", &[])]
+    };
+    let mut errors = Vec::new();
+    for (program, args) in attempts {
+        match Command::new(program).args(args).arg(mount_point).output() {
+            Ok(output) if output.status.success() => return Ok(()),
+            Ok(output) => errors.push(format!("{program}: {}", String::from_utf8_lossy(&output.stderr).trim())),
+            Err(e) => errors.push(format!("{program}: {e}")),
+        }
+    }
+    Err(errors.join("\n"))
+}
+
+/// A mount whose challenge process is gone, everything on it fails with "Transport endpoint
+/// is not connected".
+pub fn is_stale(mount_point: &Path) -> bool {
+    fs::metadata(mount_point).is_err_and(|e| e.raw_os_error() == Some(libc::ENOTCONN))
+}
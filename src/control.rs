@@ -0,0 +1,42 @@
+use crate::{hints, main_fs::{current_request, UID}, messages::{self, Message}, modules::registry};
+
+pub const CONTROL_FILE: &str = ".control";
+
+pub const USAGE: Message = Message::new("control", "usage").at(CONTROL_FILE);
+pub const ERROR: Message = Message::new("control", "error");
+pub const MESSAGES: &[Message] = &[USAGE, ERROR];
+
+/// The user that mounted the challenge, or root. Requests made outside of FUSE
+/// (from the challenge process itself) count as well.
+fn is_instructor() -> bool {
+    current_request().is_none_or(|req| req.uid == *UID || req.uid == 0)
+}
+
+/// Runs one control command. Modules can be named by id or by directory.
+pub fn run(line: &str) -> Result<String, String> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    match words.as_slice() {
+        [] => Ok(String::new()),
+        ["hint", module] => {
+            hints::request(module)?;
+            Ok(format!("Unlocked a hint for {module}"))
+        }
+        ["hint", ..] => Err("usage: hint <module>".to_string()),
+        ["reset", module] => {
+            if !is_instructor() {
+                return Err("only the user running the challenge can reset modules".to_string());
+            }
+            let module = registry::reset(module)?;
+            Ok(format!("Reset {}", module.dir))
+        }
+        ["reset", ..] => Err("usage: reset <module>".to_string()),
+        ["locale"] => Ok(format!("Current: {}\nAvailable: {}", messages::locale(), messages::locales().join(", "))),
+        ["locale", locale] => {
+            // Runs while the tree is locked, the swap happens once this write returns.
+            messages::set_locale(locale)?;
+            Ok(format!("Switched to {locale}"))
+        }
+        ["locale", ..] => Err("usage: locale [name]".to_string()),
+        [command, ..] => Err(format!("unknown command {command}")),
+    }
+}
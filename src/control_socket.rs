@@ -0,0 +1,280 @@
+use std::{fmt::Write as _, fs::{self, Permissions}, io::{self, BufRead, BufReader, Write}, mem, os::unix::{fs::PermissionsExt, io::AsRawFd, net::{UnixListener, UnixStream}}, path::{Path, PathBuf}, sync::Mutex, thread};
+
+use lazy_static::lazy_static;
+use log::{info, warn};
+
+use crate::{background_tasks::{add_file, batch, rm, rm_dir}, file_helpers::text_file, hints, main_fs::{players, with_player, UID}, messages, modules::{progression, registry}, ops, packs, progress, special_files::progress_file::{self, user_name}};
+
+pub const SOCKET_FILE: &str = "control.sock";
+/// Where `broadcast` puts its text, in every player's root.
+pub const MESSAGE_FILE: &str = "Message";
+
+pub const USAGE: &str =
+"Commands:
+    players                           every player and how far they got
+    status <uid>                      a player's .progress
+    tree <uid> [path]                 a player's files, from their root or below path
+    add <uid> <path> <name> [text]    add a file to a directory, making it if needed
+    rm <uid> <path>                   remove a file or a directory
+    unlock <uid> <module>             open a locked module
+    solve <uid> <module>              mark a module as solved
+    reset <uid> <module>              rebuild a module
+    hint <uid> <module>               unlock the next hint of a module
+    broadcast <text>                  put a Message file in every player's root
+    locale <uid> <name>               show a player the text in another language
+    reload                            load the challenge packs and messages again, see the README
+Text may use \\n for line breaks.
+";
+
+// One command per line. Every answer starts with `ok <n>` or `error <n>`, followed by its
+// n lines, so clients know where it ends.
+
+lazy_static! {
+    static ref SOCKET: Mutex<Option<PathBuf>> = {
+        Mutex::new(None)
+    };
+}
+
+/// Listens on `path` for instructors. Only the user running the challenge and root get an
+/// answer, everybody else is turned away.
+pub fn serve(path: &Path) -> Result<(), String> {
+    if UnixStream::connect(path).is_ok() {
+        return Err(format!("another challenge is listening on {}", path.display()));
+    }
+    let _ = fs::remove_file(path);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    let listener = UnixListener::bind(path).map_err(|e| e.to_string())?;
+    fs::set_permissions(path, Permissions::from_mode(0o600)).map_err(|e| e.to_string())?;
+    *SOCKET.lock().unwrap() = Some(path.to_path_buf());
+    info!("listening for control commands on {}", path.display());
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    thread::spawn(move || handle(stream));
+                }
+                Err(e) => warn!("control connection failed: {e}"),
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Removes the socket file, on shutdown.
+pub fn close() {
+    if let Some(path) = SOCKET.lock().unwrap().take() {
+        let _ = fs::remove_file(path);
+    }
+}
+
+fn handle(stream: UnixStream) {
+    let mut out = &stream;
+    if !peer_uid(&stream).is_some_and(|uid| uid == *UID || uid == 0) {
+        let _ = respond(&mut out, Err("only the user running the challenge can use the control socket".to_string()));
+        return;
+    }
+    for line in BufReader::new(&stream).lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        info!(command = line.as_str(); "control command");
+        if respond(&mut out, run(&line)).is_err() {
+            break;
+        }
+    }
+}
+
+fn peer_uid(stream: &UnixStream) -> Option<u32> {
+    let mut cred: libc::ucred = unsafe { mem::zeroed() };
+    let mut len = mem::size_of::<libc::ucred>() as libc::socklen_t;
+    let result = unsafe {
+        libc::getsockopt(stream.as_raw_fd(), libc::SOL_SOCKET, libc::SO_PEERCRED, &mut cred as *mut libc::ucred as *mut libc::c_void, &mut len)
+    };
+    (result == 0).then_some(cred.uid)
+}
+
+fn respond(out: &mut impl Write, result: Result<String, String>) -> io::Result<()> {
+    let (status, text) = match &result {
+        Ok(text) => ("ok", text),
+        Err(e) => ("error", e),
+    };
+    let lines: Vec<&str> = text.lines().collect();
+    writeln!(out, "{status} {}", lines.len())?;
+    for line in lines {
+        writeln!(out, "{line}")?;
+    }
+    out.flush()
+}
+
+/// Sends every command to the challenge listening on `path` and prints the answers.
+/// Returns whether all of them worked.
+pub fn send(path: &Path, commands: impl IntoIterator<Item = String>) -> Result<bool, String> {
+    let stream = UnixStream::connect(path).map_err(|e| format!("can not reach the challenge on {}: {e}", path.display()))?;
+    let mut lines = BufReader::new(&stream).lines();
+    let mut out = &stream;
+    let mut all_ok = true;
+    for command in commands {
+        writeln!(out, "{}", command.trim()).map_err(|e| e.to_string())?;
+        let status = lines.next().ok_or("the challenge hung up")?.map_err(|e| e.to_string())?;
+        let (status, count) = status.split_once(' ').ok_or_else(|| format!("unexpected answer {status}"))?;
+        let count: usize = count.parse().map_err(|_| format!("unexpected answer {status} {count}"))?;
+        for _ in 0..count {
+            let line = lines.next().ok_or("the challenge hung up")?.map_err(|e| e.to_string())?;
+            if status == "ok" {
+                println!("{line}");
+            } else {
+                eprintln!("{line}");
+            }
+        }
+        all_ok &= status == "ok";
+    }
+    Ok(all_ok)
+}
+
+/// Runs one instructor command. Never call it while holding `DATA`, most commands wait for
+/// their changes to be applied.
+pub fn run(line: &str) -> Result<String, String> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    match words.as_slice() {
+        [] => Ok(String::new()),
+        ["help"] => Ok(USAGE.to_string()),
+        ["players"] => Ok(list_players()),
+        ["status", uid] => Ok(with_player(player(uid)?, progress_file::render)),
+        ["tree", uid] => with_player(player(uid)?, || tree("")),
+        ["tree", uid, path] => with_player(player(uid)?, || tree(path)),
+        ["add", uid, path, name, ..] => {
+            let text = text(rest(line, 4));
+            with_player(player(uid)?, || {
+                let file = format!("{}/{name}", path.trim_end_matches('/'));
+                if ops::resolve_path(&file).is_ok() {
+                    return Err(format!("{file} already exists"));
+                }
+                add_file(path, text_file(name, &text)).wait()?;
+                Ok(format!("Added {file}"))
+            })
+        }
+        ["rm", uid, path] => with_player(player(uid)?, || {
+            rm(path).wait()?;
+            Ok(format!("Removed {path}"))
+        }),
+        ["unlock", uid, name] => with_player(player(uid)?, || {
+            let module = registry::find(name).ok_or_else(|| format!("unknown module {name}"))?;
+            if progression::is_started(&module) {
+                return Err(format!("{} is already open", module.dir));
+            }
+            progression::unlock(&module).wait()?;
+            Ok(format!("Unlocked {}", module.dir))
+        }),
+        ["solve", uid, name] => with_player(player(uid)?, || {
+            let module = registry::find(name).ok_or_else(|| format!("unknown module {name}"))?;
+            if progress::is_solved(&module.id) {
+                return Err(format!("{} is already solved", module.dir));
+            }
+            batch(|| {
+                if !progression::is_started(&module) {
+                    progression::unlock(&module);
+                }
+                registry::victory(&module.id);
+            }).wait()?;
+            Ok(format!("Solved {}", module.dir))
+        }),
+        ["reset", uid, name] => with_player(player(uid)?, || {
+            let module = registry::reset(name)?;
+            Ok(format!("Reset {}", module.dir))
+        }),
+        ["hint", uid, name] => with_player(player(uid)?, || {
+            hints::request(name)?;
+            Ok(format!("Unlocked a hint for {name}"))
+        }),
+        ["broadcast", ..] => broadcast(&text(rest(line, 1))),
+        ["locale", uid, locale] => with_player(player(uid)?, || {
+            messages::set_locale(locale)?.wait()?;
+            Ok(format!("Switched {uid} to {locale}"))
+        }),
+        ["reload"] => Ok(format!("{}\n{}", packs::reload(&packs::dir())?, messages::reload(&messages::dir())?)),
+        [command, ..] if USAGE.contains(&format!("    {command} ")) => Err(format!("wrong arguments for {command}, see help")),
+        [command, ..] => Err(format!("unknown command {command}, see help")),
+    }
+}
+
+/// A uid that has a tree, so commands never build one for a typo.
+fn player(uid: &str) -> Result<u32, String> {
+    let uid: u32 = uid.parse().map_err(|_| format!("{uid} is not a uid"))?;
+    if !players().contains(&uid) {
+        return Err(format!("no player with uid {uid}"));
+    }
+    Ok(uid)
+}
+
+/// What is left of `line` after its first `words` words, spaces and all.
+fn rest(line: &str, words: usize) -> &str {
+    let mut rest = line.trim_start();
+    for _ in 0..words {
+        rest = rest[rest.find(char::is_whitespace).unwrap_or(rest.len())..].trim_start();
+    }
+    rest.trim_end()
+}
+
+fn text(text: &str) -> String {
+    let text = text.replace("\\n", "\n");
+    if text.is_empty() || text.ends_with('\n') { text } else { text + "\n" }
+}
+
+fn list_players() -> String {
+    let progress = progress::all();
+    let modules = registry::modules().len();
+    let mut out = String::new();
+    writeln!(out, "{:<10} {:<20} {:<10} {:<10} {:<10} Hints", "Uid", "Player", "Intro", "Solved", "Attempts").unwrap();
+    for uid in players() {
+        let player = progress.get(&uid).cloned().unwrap_or_default();
+        let intro = format!("{:?}", player.intro).to_lowercase();
+        writeln!(out, "{:<10} {:<20} {:<10} {:<10} {:<10} {}", uid, user_name(uid), intro, format!("{}/{modules}", player.solved()), player.attempts(), player.hints()).unwrap();
+    }
+    out
+}
+
+/// The current player's files below `path`, one per line with their mode and size.
+fn tree(path: &str) -> Result<String, String> {
+    let ino = ops::resolve_path(path).map_err(|_| format!("{path} does not exist"))?;
+    let mut out = String::new();
+    walk(ino, 0, &mut out);
+    Ok(out)
+}
+
+fn walk(ino: u64, depth: usize, out: &mut String) {
+    let Ok(entries) = ops::readdir(ino, 0) else {
+        return;
+    };
+    for (child, _, _, name) in entries {
+        let Ok(attr) = ops::getattr(child) else {
+            continue;
+        };
+        let is_dir = attr.kind == fuser::FileType::Directory;
+        let name = name.to_string_lossy();
+        writeln!(out, "{:indent$}{name}{} {:o} {}", "", if is_dir { "/" } else { "" }, attr.perm, attr.size, indent = depth * 2).unwrap();
+        if is_dir {
+            walk(child, depth + 1, out);
+        }
+    }
+}
+
+/// Puts `text` into a `Message` file in every player's root, replacing the last one.
+fn broadcast(text: &str) -> Result<String, String> {
+    if text.is_empty() {
+        return Err("usage: broadcast <text>".to_string());
+    }
+    let players = players();
+    let pending: Vec<_> = players.iter()
+        .map(|player| with_player(*player, || batch(|| {
+            rm_dir(MESSAGE_FILE);
+            add_file("", text_file(MESSAGE_FILE, text));
+        })))
+        .collect();
+    for pending in pending {
+        pending.wait()?;
+    }
+    Ok(format!("Sent to {} players", players.len()))
+}
@@ -0,0 +1,43 @@
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+/// How hard a module is tuned. Numbers work as well, 1 is easy and 3 is hard.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    /// The value that goes with this difficulty.
+    pub fn pick<T>(self, easy: T, normal: T, hard: T) -> T {
+        match self {
+            Difficulty::Easy => easy,
+            Difficulty::Normal => normal,
+            Difficulty::Hard => hard,
+        }
+    }
+}
+
+impl FromStr for Difficulty {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "easy" | "1" => Ok(Difficulty::Easy),
+            "normal" | "2" => Ok(Difficulty::Normal),
+            "hard" | "3" => Ok(Difficulty::Hard),
+            _ => Err(format!("unknown difficulty {s}, use easy, normal, hard or 1 to 3")),
+        }
+    }
+}
+
+impl fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.pick("easy", "normal", "hard"))
+    }
+}
@@ -22,6 +22,8 @@ pub trait Dir: Send + Debug {
     fn is_user_dir(&self) -> bool;
     fn add_child(&mut self, ino: u64) -> Result<(), c_int>;
     fn remove_child(&mut self, ino: u64) -> Result<(), c_int>;
+    /// Puts `ino` back at `index`, where `get_child` found it before it was removed.
+    fn insert_child(&mut self, index: usize, ino: u64) -> Result<(), c_int>;
     fn rename(&mut self, new_name: &OsStr, in_user_dir: bool) -> Result<(), c_int>;
     fn get_child(&self, index: usize) -> Option<u64>;
     fn delete(&mut self) -> Result<(), c_int>;
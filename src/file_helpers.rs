@@ -1,6 +1,8 @@
 use std::{ffi::{c_int, OsString}, str::FromStr};
 
-use crate::{background_tasks::DEFAULT_MODE, files::File, main_fs::get_unique_ino, special_files::trigger_file::TriggerFile};
+use fuser::FileAttr;
+
+use crate::{background_tasks::DEFAULT_MODE, files::File, main_fs::get_unique_ino, special_files::{trigger_file::TriggerFile, victory_file::VictoryFile}};
 
 
 
@@ -19,15 +21,23 @@ pub fn read(data: &[u8], offset: i64, size: u32) -> Result<&[u8], c_int> {
     }
 }
 
+/// `File::replace_text` for files that keep their whole text in `data`: swaps it only if it
+/// is still exactly `old`.
+pub fn replace_text(data: &mut Vec<u8>, attr: &mut FileAttr, old: &[u8], new: &[u8]) -> bool {
+    if data != old {
+        return false;
+    }
+    *data = new.to_vec();
+    attr.size = data.len() as u64;
+    true
+}
+
 pub fn str_to_vec(str: &str) -> Vec<u8> {
     str.as_bytes().iter().map(|x| *x).collect()
 }
 
-const VICTORY_FILE_MESSAGE: &str = 
-"Congradulations you have beaten this module!
-";
-pub fn victory_file() -> Box<dyn File> {
-    text_file("Victory", VICTORY_FILE_MESSAGE)
+pub fn victory_file(module: &str) -> Box<dyn File> {
+    Box::new(VictoryFile::new(module))
 }
 
 pub fn nothing(){}
@@ -22,4 +22,7 @@ pub trait File: Send + Debug {
     fn delete(&mut self) -> Result<(), c_int>;
     fn open(&mut self, flags: i32) -> Result<u32, c_int> {Ok(flags as u32)}
     fn release(&mut self) -> Result<(), c_int> {Ok(())}
+    /// Swaps the text the file was built with for `new` if it still holds `old`, for reloads.
+    /// Returns whether it did. Files without such text never do.
+    fn replace_text(&mut self, _old: &[u8], _new: &[u8]) -> bool {false}
 }
\ No newline at end of file
@@ -0,0 +1,123 @@
+use std::{fs, io::{self, BufRead, Read, Write}, os::unix::fs::OpenOptionsExt, sync::Mutex};
+
+use hmac::{Hmac, Mac};
+use lazy_static::lazy_static;
+use log::warn;
+use sha2::Sha256;
+
+use crate::{save, special_files::progress_file::user_name};
+
+const SECRET_FILE: &str = "flag_secret";
+/// How many bytes of the HMAC end up in a flag.
+const MAC_LEN: usize = 16;
+
+lazy_static! {
+    static ref SECRET: Mutex<Option<Vec<u8>>> = {
+        Mutex::new(None)
+    };
+}
+
+/// The server secret from the state directory, created on first use.
+/// Anyone with this file can forge flags, so it is only readable by its owner.
+fn secret() -> io::Result<Vec<u8>> {
+    let mut secret = SECRET.lock().unwrap();
+    if let Some(secret) = secret.as_ref() {
+        return Ok(secret.clone());
+    }
+
+    let dir = save::state_dir();
+    let path = dir.join(SECRET_FILE);
+    let bytes = match fs::read(&path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            let mut bytes = vec![0; 32];
+            fs::File::open("/dev/urandom")?.read_exact(&mut bytes)?;
+            fs::create_dir_all(&dir)?;
+            // Private from the start, and whoever is first wins if two processes get here.
+            match fs::OpenOptions::new().write(true).create_new(true).mode(0o600).open(&path) {
+                Ok(mut file) => {
+                    file.write_all(&bytes)?;
+                    bytes
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => fs::read(&path)?,
+                Err(e) => return Err(e),
+            }
+        }
+        Err(e) => return Err(e),
+    };
+    *secret = Some(bytes.clone());
+    Ok(bytes)
+}
+
+fn mac(module: &str, uid: u32) -> io::Result<Hmac<Sha256>> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(&secret()?).expect("HMAC takes keys of any length");
+    mac.update(format!("{module}:{uid}").as_bytes());
+    Ok(mac)
+}
+
+/// The flag of `module` for the player `uid`, in the form `FLAG{module-uid-mac}`.
+pub fn flag(module: &str, uid: u32) -> String {
+    match mac(module, uid) {
+        Ok(mac) => {
+            let mac: String = mac.finalize().into_bytes()[..MAC_LEN].iter().map(|x| format!("{x:02x}")).collect();
+            format!("FLAG{{{module}-{uid}-{mac}}}")
+        }
+        Err(e) => {
+            warn!("can not read the flag secret: {e}");
+            "FLAG{unavailable}".to_string()
+        }
+    }
+}
+
+/// Checks a flag against the secret and returns the module and uid it was made for.
+pub fn verify(flag: &str) -> Result<(String, u32), String> {
+    let inner = flag.trim()
+        .strip_prefix("FLAG{")
+        .and_then(|x| x.strip_suffix('}'))
+        .ok_or("not a flag")?;
+    let mut parts = inner.rsplitn(3, '-');
+    let (Some(mac_hex), Some(uid), Some(module)) = (parts.next(), parts.next(), parts.next()) else {
+        return Err("malformed flag".to_string());
+    };
+    let uid: u32 = uid.parse().map_err(|_| "malformed uid")?;
+    let given = decode_hex(mac_hex).ok_or("malformed mac")?;
+    if given.len() != MAC_LEN {
+        return Err("malformed mac".to_string());
+    }
+
+    mac(module, uid).map_err(|e| format!("can not read the flag secret: {e}"))?
+        .verify_truncated_left(&given)
+        .map_err(|_| "wrong mac".to_string())?;
+    Ok((module.to_string(), uid))
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok()).collect()
+}
+
+/// `hack verify-flag`, reads flags from stdin when none are given. Returns the exit code,
+/// non zero if any flag is invalid or does not belong to `owner`.
+pub fn verify_command(owner: Option<u32>, mut flags: Vec<String>) -> i32 {
+    if flags.is_empty() {
+        flags = io::stdin().lock().lines().map_while(Result::ok).filter(|x| !x.trim().is_empty()).collect();
+    }
+
+    let mut failed = 0;
+    for flag in &flags {
+        match verify(flag) {
+            Ok((module, uid)) if owner.is_some_and(|owner| owner != uid) => {
+                failed += 1;
+                println!("BAD {}: {module} belongs to {} ({uid})", flag.trim(), user_name(uid));
+            }
+            Ok((module, uid)) => println!("OK  {}: {module} solved by {} ({uid})", flag.trim(), user_name(uid)),
+            Err(e) => {
+                failed += 1;
+                println!("BAD {}: {e}", flag.trim());
+            }
+        }
+    }
+    if failed > 0 { 1 } else { 0 }
+}
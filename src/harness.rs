@@ -0,0 +1,180 @@
+use std::{fs, io, path::{Path, PathBuf}, sync::Mutex, thread, time::{Duration, Instant}};
+
+use fuser::{BackgroundSession, MountOption};
+use lazy_static::lazy_static;
+
+use crate::{cli, main_fs::{self, MainFs}, modules::registry, packs, save};
+
+/// How long to wait for files that triggers add in the background.
+pub const TIMEOUT: Duration = Duration::from_secs(5);
+const POLL: Duration = Duration::from_millis(20);
+
+lazy_static! {
+    static ref DIR: PathBuf = setup();
+    static ref MOUNT: Option<Mount> = Mount::new();
+}
+
+/// Temporary directory for this process's challenge, with every module registered and
+/// the state dir inside it. The mount and the simulation both start here.
+pub fn dir() -> &'static Path {
+    &DIR
+}
+
+fn setup() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("hack-test-{}", std::process::id()));
+    fs::create_dir_all(&dir).expect("Failed to create the test directory");
+    unsafe {
+        libc::atexit(remove_dir_at_exit);
+    }
+
+    save::set_state_dir(&dir.join("state").to_string_lossy());
+    registry::register_builtin();
+    let packs = packs::load_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/packs")).expect("Failed to load challenge packs");
+    packs::install(packs);
+    dir
+}
+
+extern "C" fn remove_dir_at_exit() {
+    let _ = fs::remove_dir_all(dir());
+}
+
+/// The challenge mounted in a temporary directory, for the tests and `check-solutions`.
+/// There is only one per process, the filesystem lives in globals, so tests share it and
+/// keep out of each other's way by using their own module or their own `_` directory.
+pub struct Mount {
+    pub mount_point: PathBuf,
+    session: Mutex<Option<BackgroundSession>>,
+}
+
+/// The mounted challenge with the intro done, or `None` when FUSE is not available
+/// here (no `/dev/fuse`, no permission to mount). Tests return early in that case,
+/// unless `HACK_REQUIRE_FUSE` is set, then they fail.
+pub fn mount() -> Option<&'static Mount> {
+    let mount = MOUNT.as_ref();
+    if mount.is_none() {
+        if fuse_required() {
+            panic!("Failed to mount the challenge and {REQUIRE_FUSE} is set");
+        }
+        eprintln!("skipping, could not mount the challenge");
+    }
+    mount
+}
+
+/// Set where the mount tests must run, like CI, so a missing FUSE is not mistaken for passing tests.
+pub const REQUIRE_FUSE: &str = "HACK_REQUIRE_FUSE";
+
+pub fn fuse_required() -> bool {
+    std::env::var_os(REQUIRE_FUSE).is_some_and(|x| !x.is_empty() && x != "0")
+}
+
+extern "C" fn unmount_at_exit() {
+    if let Some(mount) = MOUNT.as_ref() {
+        mount.unmount();
+    }
+}
+
+impl Mount {
+    fn new() -> Option<Mount> {
+        let mount_point = dir().join("challenge");
+        fs::create_dir_all(&mount_point).expect("Failed to create the mount point");
+        cli::set_mount_point(mount_point.clone());
+
+        let options = [MountOption::FSName("hack-test".to_string()), MountOption::NoAtime];
+        let session = match fuser::spawn_mount2(MainFs::new(), &mount_point, &options) {
+            Ok(session) => session,
+            Err(e) => {
+                eprintln!("failed to mount {}: {e}", mount_point.display());
+                return None;
+            }
+        };
+        unsafe {
+            libc::atexit(unmount_at_exit);
+        }
+        main_fs::set_notifier(session.notifier());
+
+        let mount = Mount { mount_point, session: Mutex::new(Some(session)) };
+        if !mount.wait_for("Welcome") {
+            eprintln!("the challenge never showed up in {}", mount.mount_point.display());
+            mount.unmount();
+            return None;
+        }
+        mount.finish_intro();
+        Some(mount)
+    }
+
+    /// Reads both welcome files, like a new player would, and waits for every module.
+    fn finish_intro(&self) {
+        // Tests register packs as they go, only the modules from before the intro are built.
+        let modules = registry::modules();
+        self.read("Welcome").expect("Failed to read Welcome");
+        assert!(self.wait_for("Welcome?"), "Welcome? never showed up");
+        self.read("Welcome?").expect("Failed to read Welcome?");
+        for module in modules {
+            assert!(self.wait_for(&module.dir), "{} never showed up", module.dir);
+        }
+    }
+
+    /// Path of something inside the challenge.
+    pub fn path(&self, path: &str) -> PathBuf {
+        self.mount_point.join(path)
+    }
+
+    pub fn read(&self, path: &str) -> io::Result<String> {
+        fs::read_to_string(self.path(path))
+    }
+
+    pub fn exists(&self, path: &str) -> bool {
+        self.path(path).symlink_metadata().is_ok()
+    }
+
+    /// Waits up to `TIMEOUT` for a path to appear. Triggers add files from background threads.
+    pub fn wait_for(&self, path: &str) -> bool {
+        wait_until(|| self.exists(path))
+    }
+
+    /// Happens by itself when the process exits, before the temporary directory goes.
+    pub fn unmount(&self) {
+        if let Some(session) = self.session.lock().unwrap().take() {
+            session.join();
+        }
+    }
+}
+
+// Only the tests need these.
+#[cfg(test)]
+impl Mount {
+    /// Replaces the contents of a file, creating it if it is missing.
+    pub fn write(&self, path: &str, data: &str) -> io::Result<()> {
+        fs::write(self.path(path), data)
+    }
+
+    pub fn append(&self, path: &str, data: &str) -> io::Result<()> {
+        use std::io::Write;
+        fs::OpenOptions::new().append(true).open(self.path(path))?.write_all(data.as_bytes())
+    }
+
+    /// Names in a directory, sorted.
+    pub fn list(&self, path: &str) -> io::Result<Vec<String>> {
+        let mut names = fs::read_dir(self.path(path))?
+            .map(|entry| entry.map(|x| x.file_name().to_string_lossy().into_owned()))
+            .collect::<io::Result<Vec<_>>>()?;
+        names.sort();
+        Ok(names)
+    }
+
+    /// Waits up to `TIMEOUT` for a path to go away.
+    pub fn wait_gone(&self, path: &str) -> bool {
+        wait_until(|| !self.exists(path))
+    }
+}
+
+pub fn wait_until(mut done: impl FnMut() -> bool) -> bool {
+    let start = Instant::now();
+    while start.elapsed() < TIMEOUT {
+        if done() {
+            return true;
+        }
+        thread::sleep(POLL);
+    }
+    done()
+}
@@ -0,0 +1,109 @@
+use std::{thread, time::Duration};
+
+use serde::Deserialize;
+
+use crate::{background_tasks::add_file, difficulty::Difficulty, main_fs::{players, with_player}, messages::Message, modules::registry::{self, Module}, progress, special_files::hint_file::HintFile};
+
+/// One entry of a module's ordered hint list. Each hint only unlocks after the one
+/// before it, once enough time has passed since the module started, enough wrong
+/// attempts were made or (unless `on_request` is off) a player asked for it.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Hint {
+    pub text: String,
+    /// Where built in modules keep the text, in every language. Packs only have `text`.
+    #[serde(skip)]
+    pub message: Option<Message>,
+    #[serde(default)]
+    pub after_secs: Option<u64>,
+    #[serde(default)]
+    pub after_attempts: Option<u32>,
+    #[serde(default = "default_on_request")]
+    pub on_request: bool,
+    /// Harder modules go without this hint.
+    #[serde(default)]
+    pub max_difficulty: Option<Difficulty>,
+}
+
+fn default_on_request() -> bool {
+    true
+}
+
+impl Hint {
+    pub fn new(text: &str, after_secs: u64, after_attempts: u32) -> Self {
+        Self { text: text.to_string(), message: None, after_secs: Some(after_secs), after_attempts: Some(after_attempts), on_request: true, max_difficulty: None }
+    }
+
+    /// A hint whose text comes from the catalog, in the player's language.
+    pub fn from_message(message: Message, after_secs: u64, after_attempts: u32) -> Self {
+        Self { message: Some(message), ..Self::new("", after_secs, after_attempts) }
+    }
+
+    pub fn text(&self) -> String {
+        self.message.map(Message::text).unwrap_or_else(|| self.text.clone())
+    }
+
+    /// Only gives the hint up to `difficulty`.
+    pub fn up_to(mut self, difficulty: Difficulty) -> Self {
+        self.max_difficulty = Some(difficulty);
+        self
+    }
+}
+
+/// Reveals the next hint of the module if its conditions are met.
+pub fn check(id: &str) {
+    reveal(id, false);
+}
+
+/// Explicit request from the control file.
+pub fn request(id: &str) -> Result<(), String> {
+    let module = registry::find(id).ok_or_else(|| format!("unknown module {id}"))?;
+    if !reveal(&module.id, true) {
+        return Err(format!("no hint available for {} right now", module.dir));
+    }
+    Ok(())
+}
+
+fn reveal(id: &str, requested: bool) -> bool {
+    let Some(module) = registry::get(id) else {
+        return false;
+    };
+    let state = progress::snapshot().modules.get(id).cloned().unwrap_or_default();
+    let (Some(started), None) = (state.started, state.solved) else {
+        return false;
+    };
+    let Some(hint) = registry::hints(&module).get(state.hints as usize).cloned() else {
+        return false;
+    };
+
+    let elapsed = progress::now().saturating_sub(started);
+    let unlocked = (requested && hint.on_request)
+        || hint.after_secs.is_some_and(|secs| elapsed >= secs)
+        || hint.after_attempts.is_some_and(|attempts| state.attempts >= attempts);
+    if !unlocked {
+        return false;
+    }
+
+    if progress::hint_used(id) == 1 {
+        add_hint_file(&module);
+    }
+    true
+}
+
+pub fn add_hint_file(module: &Module) {
+    add_file(&module.dir, Box::new(HintFile::new(&module.id)));
+}
+
+/// Time based hints have nothing else to wake them up, so poll once a second.
+pub fn start_ticker() {
+    thread::spawn(|| loop {
+        thread::sleep(Duration::from_secs(1));
+        for player in players() {
+            with_player(player, || {
+                for module in registry::modules() {
+                    check(&module.id);
+                }
+            });
+        }
+    });
+}
@@ -0,0 +1,91 @@
+//! The filesystem engine behind `hack`, for binaries with challenge rooms of their own.
+//!
+//! Rooms are modules: a start function that builds their files with `add_file`, put in with
+//! `register(Module::new(..))`. Files are anything implementing `File`, `text_file` and
+//! `TriggerFile` cover the usual ones. `mount` then serves the registered modules. See
+//! `examples/`.
+
+use std::{io, path::Path};
+
+use fuser::Session;
+use log::info;
+
+pub use background_tasks::{add_file, DEFAULT_MODE};
+pub use errors::PERMISSION_DENIED;
+pub use file_helpers::{read, str_to_vec, text_file};
+pub use files::File;
+pub use hints::Hint;
+pub use main_fs::{get_unique_ino, GID, UID};
+pub use modules::{registry::{register, victory, Module}, start_mod::set_skip_intro};
+pub use special_files::trigger_file::{Trigger, TriggerFile};
+
+/// Mounts the registered modules on `mount_point` and serves them until it is unmounted.
+/// `options` are mount options like `ro`, empty for the defaults. Unlike the `hack` binary
+/// this sets up nothing else: no built in modules, packs, signals, control socket or
+/// scoreboard.
+pub fn mount(mount_point: &Path, options: &[String]) -> io::Result<()> {
+    cli::set_mount_point(mount_point.to_path_buf());
+    info!("mounting on {}", mount_point.display());
+    let mut session = Session::new(main_fs::MainFs::new(), mount_point, &cli::mount_options(options))?;
+    main_fs::set_notifier(session.notifier());
+    session.run()
+}
+
+mod cli;
+mod main_fs;
+mod files;
+mod dirs;
+mod errors;
+mod link;
+mod user_files;
+mod special_files;
+mod file_helpers;
+mod modules;
+mod background_tasks;
+mod scheduler;
+mod random;
+mod difficulty;
+mod packs;
+mod module_state;
+mod scripting;
+mod progress;
+mod save;
+mod hints;
+mod control;
+mod control_socket;
+mod flags;
+mod logging;
+mod ops;
+mod audit;
+mod replay;
+mod harness;
+mod solutions;
+mod scoreboard;
+mod shutdown;
+mod messages;
+
+/// What `src/main.rs` needs for the `hack` command line. Not part of the API, it changes
+/// with the binary.
+#[doc(hidden)]
+pub mod internal {
+    pub mod audit { pub use crate::audit::{open, read, seed}; }
+    pub mod cli { pub use crate::cli::{daemonize, is_stale, set_mount_point, unmount, Cli, Cmd, ModuleSelection, RunArgs}; }
+    pub mod control_socket { pub use crate::control_socket::{send, serve, SOCKET_FILE}; }
+    pub mod flags { pub use crate::flags::verify_command; }
+    pub mod logging { pub use crate::logging::init; }
+    pub mod messages { pub use crate::messages::{check_command, dir, install, load_dir, set_default_locale, set_dir}; }
+    pub mod packs { pub use crate::packs::{dir, install, load_dir, set_dir}; }
+    pub mod progression { pub use crate::modules::progression::set_requirements; }
+    pub mod random { pub use crate::random::{restore, set_seed}; }
+    pub mod registry { pub use crate::modules::registry::{configured_difficulty, hints, modules, register_builtin, select, set_difficulty}; }
+    pub mod replay { pub use crate::replay::replay; }
+    pub mod save { pub use crate::save::{set_state_dir, DEFAULT_STATE_DIR}; }
+    pub mod scoreboard { pub use crate::scoreboard::serve; }
+    pub mod shutdown { pub use crate::shutdown::{catch_signals, report}; }
+    pub mod solutions { pub use crate::solutions::check; }
+}
+
+#[cfg(test)]
+mod sim;
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,35 @@
+use std::{fs::OpenOptions, path::Path};
+
+use env_logger::{Builder, Target, WriteStyle};
+use log::LevelFilter;
+
+/// Sets up logging for the whole program. Only this crate follows `verbosity`, everything
+/// else stays at warnings. `filter` takes the `RUST_LOG` syntax, e.g.
+/// `hack::modules::many_open=trace`, and wins over both `verbosity` and `RUST_LOG`.
+pub fn init(verbosity: i8, filter: Option<&str>, file: Option<&Path>) -> Result<(), String> {
+    let level = match verbosity {
+        ..=-1 => LevelFilter::Warn,
+        0 => LevelFilter::Info,
+        1 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    };
+
+    let mut builder = Builder::new();
+    builder.filter_level(LevelFilter::Warn);
+    builder.filter_module("hack", level);
+    if let Ok(env) = std::env::var("RUST_LOG") {
+        builder.parse_filters(&env);
+    }
+    if let Some(filter) = filter {
+        builder.parse_filters(filter);
+    }
+
+    if let Some(file) = file {
+        let file = OpenOptions::new().create(true).append(true).open(file)
+            .map_err(|e| format!("can not open log file {}: {e}", file.display()))?;
+        builder.target(Target::Pipe(Box::new(file)));
+        builder.write_style(WriteStyle::Never);
+    }
+    builder.format_timestamp_millis();
+    builder.try_init().map_err(|e| e.to_string())
+}
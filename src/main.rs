@@ -1,29 +1,128 @@
-use std::fs::create_dir;
+use std::{env, io, path::Path, process::exit};
 
-use fuser::{mount2, MountOption};
+use clap::Parser;
+use log::{info, warn};
+
+use hack::internal::{audit, cli::{self, Cli, Cmd, ModuleSelection, RunArgs}, control_socket, flags, logging, messages, packs, progression, random, registry, replay, save, scoreboard, shutdown, solutions};
 
-pub const MOUNT_POINT: &str = "./challenge";
 fn main() {
-    let fs = main_fs::MainFs::new();
-    println!("mounting");
-    
-    // warn!("Warning");
-
-    mount2(fs, MOUNT_POINT, &[
-        MountOption::AllowOther,
-        MountOption::AutoUnmount,
-        MountOption::Exec,
-        MountOption::NoAtime,
-    ]).expect("Failed to mount fs");
+    let cli = Cli::parse();
+    if let Err(e) = logging::init(cli.verbosity(), cli.log.as_deref(), cli.log_file.as_deref()) {
+        eprintln!("Failed to set up logging: {e}");
+        exit(2);
+    }
+    let state_dir = cli.state_dir.clone()
+        .or_else(|| env::var_os("HACK_STATE_DIR").map(Into::into))
+        .unwrap_or(save::DEFAULT_STATE_DIR.into());
+    save::set_state_dir(&state_dir.to_string_lossy());
+    let control_socket = cli.control_socket.clone()
+        .or_else(|| env::var_os("HACK_CONTROL_SOCKET").map(Into::into))
+        .unwrap_or_else(|| state_dir.join(control_socket::SOCKET_FILE));
+    if let Some(seed) = cli.seed.or_else(|| env::var("HACK_SEED").ok()?.parse().ok()) {
+        random::set_seed(seed);
+    }
+
+    match cli.command.clone() {
+        None => run(&cli, cli.run.clone(), &control_socket),
+        Some(Cmd::Run(args)) => run(&cli, args, &control_socket),
+        Some(Cmd::Modules { select }) => {
+            load_modules(&cli, &select);
+            for module in registry::modules() {
+                let difficulty = registry::configured_difficulty(&module.id);
+                let requires = if module.requires.is_empty() { String::new() } else { format!(", requires {}", module.requires.join(" ")) };
+                println!("{:<20} {:<20} {:<8} {} hints{}{requires}", module.id, module.dir, difficulty, registry::hints(&module).len(), if module.shared { ", shared" } else { "" });
+            }
+        }
+        Some(Cmd::VerifyFlag { uid, flags }) => exit(flags::verify_command(uid, flags)),
+        Some(Cmd::Replay { audit_log, uid, select, skip_intro }) => {
+            let records = audit::read(&audit_log).unwrap_or_else(|e| panic!("Failed to read the audit log: {e}"));
+            // `--seed` and `HACK_SEED` still win.
+            random::restore(audit::seed(&records));
+            // Never touch the real saves.
+            let state_dir = env::temp_dir().join(format!("hack-replay-{}", std::process::id()));
+            save::set_state_dir(&state_dir.to_string_lossy());
+            load_modules(&cli, &select);
+            hack::set_skip_intro(skip_intro);
+            let diverged = replay::replay(&records, uid);
+            let _ = std::fs::remove_dir_all(state_dir);
+            exit(if diverged > 0 { 1 } else { 0 });
+        }
+        Some(Cmd::Control { command }) => {
+            let commands: Vec<String> = if command.is_empty() {
+                io::stdin().lines().map_while(Result::ok).collect()
+            } else {
+                vec![command.join(" ")]
+            };
+            match control_socket::send(&control_socket, commands) {
+                Ok(all_ok) => exit(if all_ok { 0 } else { 1 }),
+                Err(e) => {
+                    eprintln!("{e}");
+                    exit(2);
+                }
+            }
+        }
+        Some(Cmd::CheckSolutions { dir }) => exit(solutions::check(&dir)),
+        Some(Cmd::CheckMessages) => exit(messages::check_command(&cli.messages)),
+        Some(Cmd::Unmount { mount_point, lazy }) => {
+            if let Err(e) = cli::unmount(&mount_point, lazy) {
+                eprintln!("Failed to unmount {}:\n{e}", mount_point.display());
+                exit(1);
+            }
+        }
+    }
+}
+
+fn load_modules(cli: &Cli, select: &ModuleSelection) {
+    registry::register_builtin();
+    packs::set_dir(&cli.packs.to_string_lossy());
+    let packs = packs::load_dir(&packs::dir()).unwrap_or_else(|e| panic!("Failed to load challenge packs: {e}"));
+    info!("loaded {} challenge packs", packs.len());
+    packs::install(packs);
+    messages::set_dir(&cli.messages.to_string_lossy());
+    let catalogs = messages::load_dir(&messages::dir()).unwrap_or_else(|e| panic!("Failed to load messages: {e}"));
+    messages::install(catalogs);
+    let locale = cli.locale.clone().or_else(|| env::var("HACK_LOCALE").ok());
+    let selected = registry::select(&select.modules, &select.exclude)
+        .and_then(|_| registry::set_difficulty(&select.difficulty))
+        .and_then(|_| progression::set_requirements(&select.requires))
+        .and_then(|_| locale.map_or(Ok(()), |locale| messages::set_default_locale(&locale)));
+    if let Err(e) = selected {
+        eprintln!("{e}");
+        exit(2);
+    }
+}
+
+fn run(cli: &Cli, args: RunArgs, control_socket: &Path) {
+    load_modules(cli, &args.select);
+    hack::set_skip_intro(args.skip_intro);
+    cli::set_mount_point(args.mount_point.clone());
+    if args.daemon {
+        cli::daemonize();
+    }
+    shutdown::catch_signals(args.mount_point.clone());
+
+    if cli::is_stale(&args.mount_point) {
+        warn!("{} is left over from a challenge that did not shut down, unmounting it", args.mount_point.display());
+        if let Err(e) = cli::unmount(&args.mount_point, true) {
+            eprintln!("Failed to unmount the stale {}:\n{e}", args.mount_point.display());
+            exit(1);
+        }
+    }
+
+    if let Some(addr) = args.scoreboard_addr.clone().or_else(|| env::var("HACK_SCOREBOARD_ADDR").ok()) {
+        scoreboard::serve(&addr, args.scoreboard_public).unwrap_or_else(|e| panic!("Failed to serve the scoreboard on {addr}: {e}"));
+    }
+
+    control_socket::serve(control_socket).unwrap_or_else(|e| panic!("Failed to listen on {}: {e}", control_socket.display()));
+
+    if let Some(path) = &args.audit_log {
+        audit::open(path).unwrap_or_else(|e| panic!("Failed to open the audit log {}: {e}", path.display()));
+    }
+
+    if let Err(e) = hack::mount(&args.mount_point, &args.options) {
+        eprintln!("Failed to mount on {}: {e}", args.mount_point.display());
+        exit(1);
+    }
+    shutdown::report();
 }
 
-mod main_fs;
-mod files;
-mod dirs;
-mod errors;
-mod link;
-mod user_files;
-mod special_files;
-mod file_helpers;
-mod modules;
-mod background_tasks;
\ No newline at end of file
@@ -1,21 +1,24 @@
-use std::{collections::HashMap, ffi::{c_int, OsStr, OsString}, os::unix::ffi::OsStrExt, str::FromStr, sync::{atomic::AtomicU64, Mutex}, time::Duration};
+use std::{cell::Cell, collections::HashMap, ffi::{c_int, OsStr, OsString}, str::FromStr, sync::{atomic::AtomicU64, Arc, Mutex}, thread, time::Duration};
 
-use fuser::{FileAttr, Filesystem, KernelConfig, Request};
+use fuser::{FileAttr, Filesystem, KernelConfig, Notifier, Request, FUSE_ROOT_ID};
 use lazy_static::lazy_static;
+use log::{debug, info, trace, warn};
 use users::{get_current_gid, get_current_uid, UsersCache};
 
-use crate::{dirs::Dir, errors::{NOT_SUPPORTED, PERMISSION_DENIED}, files::File, link::Link, modules::start_mod, user_files::{NormalDir, UserFile}};
+use crate::{audit::{self, Record}, background_tasks::batch, dirs::Dir, errors::NOT_SUPPORTED, files::File, link::Link, hints, modules::start_mod, ops, save, shutdown, user_files::NormalDir};
 
 const DEFAULT_CACHE: Duration = Duration::ZERO;
 
 static NEXT_INO: AtomicU64 = AtomicU64::new(2);
 
+/// Owner of the directories modules build for everyone, see `registry::Module::shared`.
+pub const SHARED_PLAYER: u32 = u32::MAX;
+
 lazy_static! {
     pub static ref DATA: Mutex<FsData> = {
-        let mut inos = HashMap::new();
-        inos.insert(1, Ino::Dir(Box::new(NormalDir::new(&OsString::from_str("root").unwrap(), false, 1, 0o777, *UID, *GID, 0))));
         Mutex::new(FsData {
-            inos
+            inos: HashMap::new(),
+            roots: HashMap::new(),
         })
     };
     
@@ -25,10 +28,26 @@ lazy_static! {
     pub static ref GID: u32 = {
         get_current_gid()
     };
+    static ref NOTIFIER: Mutex<Option<Arc<Notifier>>> = {
+        Mutex::new(None)
+    };
+}
+
+thread_local! {
+    static REQUEST: Cell<Option<RequestInfo>> = const { Cell::new(None) };
+    static PLAYER: Cell<Option<u32>> = const { Cell::new(None) };
 }
 
 pub struct MainFs {}
 
+/// Who made the request currently being handled, for files and triggers that care.
+#[derive(Debug, Clone, Copy)]
+pub struct RequestInfo {
+    pub uid: u32,
+    pub gid: u32,
+    pub pid: u32,
+}
+
 #[derive(Debug)]
 pub enum Ino {
     File(Box<dyn File>),
@@ -130,12 +149,42 @@ impl Ino {
     }
 }
 
+/// Every player gets their own root directory, the kernel only ever sees it as ino 1.
 #[derive(Debug)]
 pub struct FsData{
     pub inos: HashMap<u64, Ino>,
+    pub roots: HashMap<u32, u64>,
     // fhs: HashMap<u64, Ino>,
 }
 
+impl FsData {
+    pub fn root(&mut self, player: u32) -> u64 {
+        if let Some(root) = self.roots.get(&player) {
+            return *root;
+        }
+
+        let ino = get_unique_ino();
+        self.inos.insert(ino, Ino::Dir(Box::new(NormalDir::new(&OsString::from_str("root").unwrap(), false, ino, 0o777, *UID, *GID, 0))));
+        self.roots.insert(player, ino);
+        ino
+    }
+
+    /// Maps the kernel's root ino to the current player's root.
+    pub fn resolve(&mut self, ino: u64) -> u64 {
+        if ino == FUSE_ROOT_ID {
+            self.root(current_player())
+        } else {
+            ino
+        }
+    }
+}
+
+impl Default for MainFs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl MainFs {
     pub fn new() -> MainFs {
         MainFs {
@@ -145,21 +194,33 @@ impl MainFs {
 
 impl Filesystem for MainFs {
     fn init(&mut self, _req: &Request<'_>, _config: &mut KernelConfig) -> Result<(), c_int> {
-        println!("Init");
-        start_mod::start();
+        debug!("init");
+        if save::load() {
+            info!("restored progress from {}", save::state_dir().display());
+        }
+        audit::session();
+        hints::start_ticker();
         Ok(())
     }
 
     fn destroy(&mut self) {
-        println!("Destroy");
+        shutdown::finish();
+        info!("unmounted");
     }
 
-    fn lookup(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: fuser::ReplyEntry) {
-        //println!("Lookup {parent}:{name:?}");
-        let data = DATA.lock().unwrap();
-        match lookup(parent, name, &data) {
-            Ok(cattr) => reply.entry(&DEFAULT_CACHE, cattr, 0),
-            Err(err) => reply.error(err),
+    fn lookup(&mut self, req: &Request<'_>, parent: u64, name: &OsStr, reply: fuser::ReplyEntry) {
+        enter(req, "lookup", parent);
+        let path = audit::enabled().then(|| ops::child_path(parent, name)).flatten();
+        let result = ops::lookup(parent, name);
+        if audit::enabled() {
+            if let (Some(path), Ok(attr)) = (&path, &result) {
+                ops::remember_path(attr.ino, path);
+            }
+            audit::record(Record::new("lookup", path, &result));
+        }
+        match result {
+            Ok(cattr) => reply.entry(&DEFAULT_CACHE, &cattr, 0),
+            Err(err) => reply.error(failed("lookup", parent, err)),
         }
     }
 
@@ -167,18 +228,22 @@ impl Filesystem for MainFs {
         
     // }
 
-    fn getattr(&mut self, _req: &Request<'_>, ino: u64, reply: fuser::ReplyAttr) {
-        println!("Getattr: {ino}");
-        reply.attr(&DEFAULT_CACHE, DATA.lock().unwrap().inos.get(&ino).unwrap().attr());
-        // match DATA.lock().unwrap().inos.get(&ino).unwrap().attr() {
-        //     Ok(attr) => reply.attr(&DEFAULT_CACHE, attr),
-        //     Err(e) => reply.error(e),
-        // }
+    fn getattr(&mut self, req: &Request<'_>, ino: u64, reply: fuser::ReplyAttr) {
+        enter(req, "getattr", ino);
+        let path = audit::enabled().then(|| ops::path_of(ino)).flatten();
+        let result = ops::getattr(ino);
+        if audit::enabled() {
+            audit::record(Record::new("getattr", path, &result));
+        }
+        match result {
+            Ok(attr) => reply.attr(&DEFAULT_CACHE, &attr),
+            Err(e) => reply.error(failed("getattr", ino, e)),
+        }
     }
 
     fn setattr(
             &mut self,
-            _req: &Request<'_>,
+            req: &Request<'_>,
             ino: u64,
             mode: Option<u32>,
             uid: Option<u32>,
@@ -194,13 +259,15 @@ impl Filesystem for MainFs {
             flags: Option<u32>,
             reply: fuser::ReplyAttr,
         ) {
-        println!("setattr: {ino}");
-        let mut data = DATA.lock().unwrap();
-        let target = data.inos.get_mut(&ino).unwrap();
-        if let Err(e) = target.setattr(mode, uid, gid, size, fh, flags) {
-            reply.error(e);
-        } else {
-            reply.attr(&DEFAULT_CACHE, target.attr())
+        enter(req, "setattr", ino);
+        let path = audit::enabled().then(|| ops::path_of(ino)).flatten();
+        let result = ops::setattr(ino, mode, uid, gid, size, fh, flags);
+        if audit::enabled() {
+            audit::record(Record { mode, set_size: size, ..Record::new("setattr", path, &result) });
+        }
+        match result {
+            Ok(attr) => reply.attr(&DEFAULT_CACHE, &attr),
+            Err(e) => reply.error(failed("setattr", ino, e)),
         }
     }
 
@@ -214,24 +281,19 @@ impl Filesystem for MainFs {
             rdev: u32,
             reply: fuser::ReplyEntry,
         ) {
-        println!("mknod: {parent} name: {name:?}");
-        let mut data = DATA.lock().unwrap();
-        let parent = data.inos.get_mut(&parent).unwrap().unwrap_dir_mut();
-        if !parent.is_user_dir() && name.as_bytes()[0] != b'_' {
-            reply.error(PERMISSION_DENIED);
-            return;
+        enter(req, "mknod", parent);
+        let path = audit::enabled().then(|| ops::child_path(parent, name)).flatten();
+        let result = ops::mknod(parent, name, mode);
+        if audit::enabled() {
+            if let (Some(path), Ok(attr)) = (&path, &result) {
+                ops::remember_path(attr.ino, path);
+            }
+            audit::record(Record { mode: Some(mode), ..Record::new("mknod", path, &result) });
         }
-
-        let new_ino = get_unique_ino();
-        if let Err(e) = parent.add_child(new_ino) {
-            reply.error(e);
-            return;
+        match result {
+            Ok(attr) => reply.entry(&DEFAULT_CACHE, &attr, 0),
+            Err(e) => reply.error(failed("mknod", parent, e)),
         }
-
-        let new_user_file = UserFile::new(name, new_ino, 0, mode, req.uid(), req.gid(), 0);
-        data.inos.insert(new_ino, Ino::File(Box::new(new_user_file)));
-
-        reply.entry(&DEFAULT_CACHE, data.inos.get(&new_ino).unwrap().attr(), 0);
     }
 
     fn mkdir(
@@ -243,64 +305,50 @@ impl Filesystem for MainFs {
             umask: u32,
             reply: fuser::ReplyEntry,
         ) {
-        println!("mkdir: {parent} name: {name:?}");
-        let mut data = DATA.lock().unwrap();
-        let parent = data.inos.get_mut(&parent).unwrap().unwrap_dir_mut();
-        let is_user_dir = parent.is_user_dir() || name.as_bytes()[0] == b'_';
-        if !parent.is_user_dir() && name.as_bytes()[0] != b'_' {
-            reply.error(PERMISSION_DENIED);
-            return;
+        enter(req, "mkdir", parent);
+        let path = audit::enabled().then(|| ops::child_path(parent, name)).flatten();
+        let result = ops::mkdir(parent, name, mode);
+        if audit::enabled() {
+            if let (Some(path), Ok(attr)) = (&path, &result) {
+                ops::remember_path(attr.ino, path);
+            }
+            audit::record(Record { mode: Some(mode), ..Record::new("mkdir", path, &result) });
         }
-
-        let new_ino = get_unique_ino();
-        if let Err(e) = parent.add_child(new_ino) {
-            reply.error(e);
-            return;
+        match result {
+            Ok(attr) => reply.entry(&DEFAULT_CACHE, &attr, 0),
+            Err(e) => reply.error(failed("mkdir", parent, e)),
         }
-
-        let new_dir = NormalDir::new(name, is_user_dir, new_ino, mode, req.uid(), req.gid(), 0);
-        data.inos.insert(new_ino, Ino::Dir(Box::new(new_dir)));
-
-        reply.entry(&DEFAULT_CACHE, data.inos.get(&new_ino).unwrap().attr(), 0);
     }
 
-    fn unlink(&mut self, _req: &Request<'_>, parent: u64, name: &std::ffi::OsStr, reply: fuser::ReplyEmpty) {
-        println!("Unlink");
-        let mut data = DATA.lock().unwrap();
-        let child = data.inos.get(&parent).unwrap().unwrap_dir().lookup_child(name, &data.inos).unwrap();
-        if let Err(e) = data.inos.get_mut(&parent).unwrap().unwrap_dir_mut().remove_child(child) {
-            println!("ERROR 2");
-            reply.error(e);
-            return;
-        }
-
-        if let Err(e) = data.inos.get_mut(&child).unwrap().delete() {
-            if data.inos.get_mut(&parent).unwrap().unwrap_dir_mut().add_child(child).is_err() {
-                println!("Warning: unlink lost a file");
+    fn unlink(&mut self, req: &Request<'_>, parent: u64, name: &std::ffi::OsStr, reply: fuser::ReplyEmpty) {
+        enter(req, "unlink", parent);
+        let path = audit::enabled().then(|| ops::child_path(parent, name)).flatten();
+        let result = ops::unlink(parent, name);
+        if audit::enabled() {
+            if let (Some(path), Ok(())) = (&path, &result) {
+                ops::forget_path(path);
             }
-            println!("ERROR 1");
-            reply.error(e);
-            return;
+            audit::record(Record::new("unlink", path, &result));
+        }
+        match result {
+            Ok(()) => reply.ok(),
+            Err(e) => reply.error(failed("unlink", parent, e)),
         }
-
-        data.inos.remove(&child);
-        reply.ok();
     }
 
-    fn rmdir(&mut self, _req: &Request<'_>, parent: u64, name: &std::ffi::OsStr, reply: fuser::ReplyEmpty) {
-        println!("rmdir: {parent} {name:?}");
-        let mut data = DATA.lock().unwrap();
-        let child_ino = match data.inos.get(&parent).unwrap().unwrap_dir().lookup_child(name, &data.inos) {
-            Ok(child_ino) => child_ino,
-            Err(e) => {
-                reply.error(e);
-                return;
+    fn rmdir(&mut self, req: &Request<'_>, parent: u64, name: &std::ffi::OsStr, reply: fuser::ReplyEmpty) {
+        enter(req, "rmdir", parent);
+        let path = audit::enabled().then(|| ops::child_path(parent, name)).flatten();
+        let result = ops::rmdir(parent, name);
+        if audit::enabled() {
+            if let (Some(path), Ok(())) = (&path, &result) {
+                ops::forget_path(path);
             }
-        };
-        if let Err(e) = data.inos.get_mut(&parent).unwrap().unwrap_dir_mut().remove_child(child_ino) {
-            reply.error(e);
-        } else {
-            reply.ok();
+            audit::record(Record::new("rmdir", path, &result));
+        }
+        match result {
+            Ok(()) => reply.ok(),
+            Err(e) => reply.error(failed("rmdir", parent, e)),
         }
     }
 
@@ -317,7 +365,7 @@ impl Filesystem for MainFs {
 
     fn rename(
             &mut self,
-            _req: &Request<'_>,
+            req: &Request<'_>,
             parent: u64,
             name: &std::ffi::OsStr,
             newparent: u64,
@@ -325,35 +373,20 @@ impl Filesystem for MainFs {
             flags: u32,
             reply: fuser::ReplyEmpty,
         ) {
-        println!("Rename: par: {parent} name: {name:?} newparent: {newparent} newname: {newname:?}");
-        let mut data = DATA.lock().unwrap();   
-        let child_ino = match data.inos.get(&parent).unwrap().unwrap_dir().lookup_child(name, &data.inos) {
-            Ok(child_ino) => child_ino,
-            Err(e) => {
-                reply.error(e);
-                return;
+        enter(req, "rename", parent);
+        let path = audit::enabled().then(|| ops::child_path(parent, name)).flatten();
+        let new_path = audit::enabled().then(|| ops::child_path(newparent, newname)).flatten();
+        let result = ops::rename(parent, name, newparent, newname);
+        if audit::enabled() {
+            if let (Some(path), Ok(())) = (&path, &result) {
+                ops::forget_path(path);
             }
-        };
-
-        let new_dir_is_user_dir = data.inos.get(&parent).unwrap().unwrap_dir().is_user_dir();
-
-        if let Err(e) = data.inos.get_mut(&child_ino).unwrap().rename(newname, new_dir_is_user_dir) {
-            reply.error(e);
-            return;
+            audit::record(Record { new_path, ..Record::new("rename", path, &result) });
         }
-        if let Err(e) = data.inos.get_mut(&parent).unwrap().unwrap_dir_mut().remove_child(child_ino) {
-            reply.error(e);
-            return;
-        }
-        if let Err(e) = data.inos.get_mut(&newparent).unwrap().unwrap_dir_mut().add_child(child_ino) {
-            reply.error(e);
-            if data.inos.get_mut(&parent).unwrap().unwrap_dir_mut().add_child(child_ino).is_err() {
-                println!("Warning: Rename dropped a file");
-            }
-            return;
+        match result {
+            Ok(()) => reply.ok(),
+            Err(e) => reply.error(failed("rename", parent, e)),
         }
-        
-        reply.ok();
     }
 
     // fn link(
@@ -367,17 +400,22 @@ impl Filesystem for MainFs {
         
     // }
 
-    fn open(&mut self, _req: &Request<'_>, ino: u64, flags: i32, reply: fuser::ReplyOpen) {
-        let mut data: std::sync::MutexGuard<FsData> = DATA.lock().unwrap();
-        match data.inos.get_mut(&ino).unwrap().unwrap_file_mut().open(flags) {
-            Ok(flags) => reply.opened(get_unique_ino(), flags),
-            Err(e) => reply.error(e),
+    fn open(&mut self, req: &Request<'_>, ino: u64, flags: i32, reply: fuser::ReplyOpen) {
+        enter(req, "open", ino);
+        let path = audit::enabled().then(|| ops::path_of(ino)).flatten();
+        let result = ops::open(ino, flags);
+        if audit::enabled() {
+            audit::record(Record { flags: Some(flags), ..Record::new("open", path, &result) });
+        }
+        match result {
+            Ok((fh, flags)) => reply.opened(fh, flags),
+            Err(e) => reply.error(failed("open", ino, e)),
         }
     }
 
     fn read(
             &mut self,
-            _req: &Request<'_>,
+            req: &Request<'_>,
             ino: u64,
             fh: u64,
             offset: i64,
@@ -386,22 +424,23 @@ impl Filesystem for MainFs {
             lock_owner: Option<u64>,
             reply: fuser::ReplyData,
         ) {
-        println!("Read: {ino} off: {offset} size: {size}");
-        let mut data: std::sync::MutexGuard<FsData> = DATA.lock().unwrap();
-        let file = data.inos.get_mut(&ino).unwrap();
-        if let Some(file) = file.try_unwrap_file_mut() {
-            match file.read(offset, size, flags) {
-                Ok(data) => reply.data(data),
-                Err(e) => reply.error(e),
-            }
-        } else {
-            reply.error(NOT_SUPPORTED);
+        enter(req, "read", ino);
+        trace!(offset = offset, size = size; "read");
+        let path = audit::enabled().then(|| ops::path_of(ino)).flatten();
+        let result = ops::read(ino, offset, size, flags);
+        if audit::enabled() {
+            let len = result.as_ref().ok().map(|data| data.len() as u64);
+            audit::record(Record { offset: Some(offset), size: Some(size), len, flags: Some(flags), ..Record::new("read", path, &result) });
+        }
+        match result {
+            Ok(data) => reply.data(&data),
+            Err(e) => reply.error(failed("read", ino, e)),
         }
     }
 
     fn write(
             &mut self,
-            _req: &Request<'_>,
+            req: &Request<'_>,
             ino: u64,
             fh: u64,
             offset: i64,
@@ -411,16 +450,17 @@ impl Filesystem for MainFs {
             lock_owner: Option<u64>,
             reply: fuser::ReplyWrite,
         ) {
-        println!("Write: {ino} off: {offset} len: {}", write_data.len());
-        let mut data = DATA.lock().unwrap();
-        let file = data.inos.get_mut(&ino).unwrap();
-        if let Some(file) = file.try_unwrap_file_mut() {
-            match file.write(offset, write_data, write_flags, flags) {
-                Ok(amount) => reply.written(amount),
-                Err(e) => reply.error(e),
-            }
-        } else {
-            reply.error(NOT_SUPPORTED);
+        enter(req, "write", ino);
+        trace!(offset = offset, len = write_data.len(); "write");
+        let path = audit::enabled().then(|| ops::path_of(ino)).flatten();
+        let result = ops::write(ino, offset, write_data, write_flags, flags);
+        if audit::enabled() {
+            let len = result.as_ref().ok().map(|amount| *amount as u64);
+            audit::record(Record { offset: Some(offset), len, flags: Some(flags), write_flags: Some(write_flags), data: Some(write_data.to_vec()), ..Record::new("write", path, &result) });
+        }
+        match result {
+            Ok(amount) => reply.written(amount),
+            Err(e) => reply.error(failed("write", ino, e)),
         }
     }
 
@@ -430,7 +470,7 @@ impl Filesystem for MainFs {
 
     fn release(
             &mut self,
-            _req: &Request<'_>,
+            req: &Request<'_>,
             ino: u64,
             _fh: u64,
             _flags: i32,
@@ -438,11 +478,15 @@ impl Filesystem for MainFs {
             _flush: bool,
             reply: fuser::ReplyEmpty,
         ) {
-        println!("RELEASE: ----------------------------------");
-        let mut data: std::sync::MutexGuard<FsData> = DATA.lock().unwrap();
-        match data.inos.get_mut(&ino).unwrap().unwrap_file_mut().release() {
+        enter(req, "release", ino);
+        let path = audit::enabled().then(|| ops::path_of(ino)).flatten();
+        let result = ops::release(ino);
+        if audit::enabled() {
+            audit::record(Record::new("release", path, &result));
+        }
+        match result {
             Ok(()) => reply.ok(),
-            Err(e) => reply.error(e),
+            Err(e) => reply.error(failed("release", ino, e)),
         }
     }
 
@@ -456,34 +500,29 @@ impl Filesystem for MainFs {
 
     fn readdir(
             &mut self,
-            _req: &Request<'_>,
+            req: &Request<'_>,
             ino: u64,
             fh: u64,
             offset: i64,
             mut reply: fuser::ReplyDirectory,
         ) {
-        println!("Read Dir: {ino} off:{offset}");
-        let data = DATA.lock().unwrap();
-        let dir = data.inos.get(&ino).unwrap().unwrap_dir();
-        
-        let Ok(mut offset) = offset.try_into() else {
-            reply.error(NOT_SUPPORTED);
-            return;
-        };
-        loop {
-            let Some(child) = dir.get_child(offset) else {
-                println!("Break because not found");
-                break;
-            };
-            let child_ino = data.inos.get(&child).unwrap();
-            if reply.add(child, offset as i64 + 1, child_ino.attr().kind, child_ino.name()) {
-                println!("Full");
-                break;
+        enter(req, "readdir", ino);
+        let path = audit::enabled().then(|| ops::path_of(ino)).flatten();
+        let result = offset.try_into().map_err(|_| NOT_SUPPORTED).and_then(|offset| ops::readdir(ino, offset));
+        if audit::enabled() {
+            audit::record(Record { offset: Some(offset), ..Record::new("readdir", path, &result) });
+        }
+        match result {
+            Ok(entries) => {
+                for (child, next, kind, name) in entries {
+                    if reply.add(child, next, kind, name) {
+                        break;
+                    }
+                }
+                reply.ok();
             }
-            println!("Added");
-            offset += 1;
+            Err(e) => reply.error(failed("readdir", ino, e)),
         }
-        reply.ok();
     }
 
     // fn releasedir(
@@ -512,12 +551,90 @@ impl Filesystem for MainFs {
 
 }
 
+fn enter(req: &Request<'_>, op: &str, ino: u64) {
+    trace!(op = op, ino = ino, uid = req.uid(), pid = req.pid(); "{op}");
+    shutdown::count(op, req.uid());
+    begin_request(RequestInfo { uid: req.uid(), gid: req.gid(), pid: req.pid() });
+}
+
+/// Makes `req` the request being handled on this thread, building the player's tree if needed.
+pub fn begin_request(req: RequestInfo) {
+    REQUEST.with(|current| current.set(Some(req)));
+    ensure_player(req.uid);
+}
+
+/// Logs a failed operation on its way back to the kernel.
+fn failed(op: &str, ino: u64, errno: c_int) -> c_int {
+    let uid = current_request().map(|req| req.uid);
+    let pid = current_request().map(|req| req.pid);
+    debug!(op = op, ino = ino, uid = uid, pid = pid, errno = errno; "{op} failed");
+    errno
+}
+
+pub fn current_request() -> Option<RequestInfo> {
+    REQUEST.with(|current| current.get())
+}
+
+/// The player whose tree is being worked on. Outside of requests and player
+/// tasks this is the user running the challenge.
+pub fn current_player() -> u32 {
+    PLAYER.with(|player| player.get())
+        .or_else(|| current_request().map(|req| req.uid))
+        .unwrap_or(*UID)
+}
+
+pub fn with_player<T>(player: u32, f: impl FnOnce() -> T) -> T {
+    let previous = PLAYER.with(|current| current.replace(Some(player)));
+    let out = f();
+    PLAYER.with(|current| current.set(previous));
+    out
+}
+
+/// Lets `invalidate` reach the kernel of this mount.
+pub fn set_notifier(notifier: Notifier) {
+    *NOTIFIER.lock().unwrap() = Some(Arc::new(notifier));
+}
+
+/// Makes the kernel drop what it cached of these files. Runs on a thread of its own, the
+/// kernel may need a request answered before it lets go.
+pub fn invalidate(inos: Vec<u64>) {
+    let Some(notifier) = NOTIFIER.lock().unwrap().clone() else {
+        return;
+    };
+    thread::spawn(move || {
+        for ino in inos {
+            if let Err(e) = notifier.inval_inode(ino, 0, 0) {
+                debug!(ino = ino; "failed to invalidate: {e}");
+            }
+        }
+    });
+}
+
+/// Builds a player's tree the first time they touch the mount.
+pub fn ensure_player(player: u32) {
+    let mut data = DATA.lock().unwrap();
+    if data.roots.contains_key(&player) {
+        return;
+    }
+    data.root(player);
+    drop(data);
+
+    info!(uid = player; "new player");
+    // Wait for the tree so the player's first request already sees it.
+    if let Err(e) = with_player(player, || batch(start_mod::start)).wait() {
+        warn!(uid = player; "failed to build the tree of a new player: {e}");
+    }
+}
+
+pub fn players() -> Vec<u32> {
+    DATA.lock().unwrap().roots.keys().copied().filter(|player| *player != SHARED_PLAYER).collect()
+}
+
 pub fn get_unique_ino() -> u64 {
     NEXT_INO.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
 }
 
 pub fn lookup<'a>(parent: u64, name: &OsStr, data: &'a FsData) -> Result<&'a FileAttr, c_int> {
-    //println!("Lookup {parent}:{name:?}");
     match data.inos.get(&parent).unwrap().unwrap_dir().lookup_child(name, &data.inos) {
         Ok(cino) => return Ok(data.inos.get(&cino).unwrap().attr()),
         Err(err) => return Err(err),
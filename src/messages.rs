@@ -0,0 +1,260 @@
+use std::{collections::BTreeMap, fmt::Display, fs, path::Path, sync::Mutex};
+
+use lazy_static::lazy_static;
+use log::warn;
+
+use crate::{background_tasks::{batch, replace_text, Pending}, control, main_fs::{players, with_player}, modules::{bathroom, classroom, correct_order, find_the_suid, kill_them_all, many_open, progression, start_mod}, progress, scoreboard, special_files::{hint_file, progress_file, victory_file}};
+
+pub const MESSAGES_DIR: &str = "./messages";
+pub const DEFAULT_LOCALE: &str = "en";
+/// Always there, so every message has a text to fall back to.
+const ENGLISH: &str = include_str!("../messages/en.toml");
+
+/// Text by module id and message id, one per locale.
+pub type Catalog = BTreeMap<String, BTreeMap<String, String>>;
+
+lazy_static! {
+    static ref CATALOGS: Mutex<Catalogs> = {
+        Mutex::new(Catalogs {
+            dir: MESSAGES_DIR.to_string(),
+            default: DEFAULT_LOCALE.to_string(),
+            locales: BTreeMap::from([(DEFAULT_LOCALE.to_string(), parse(ENGLISH).expect("Failed to parse the built in messages"))]),
+        })
+    };
+}
+
+#[derive(Debug)]
+struct Catalogs {
+    dir: String,
+    /// For players that did not pick one.
+    default: String,
+    locales: BTreeMap<String, Catalog>,
+}
+
+/// A piece of story text, looked up in the catalog of the current player's locale.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Message {
+    pub module: &'static str,
+    pub id: &'static str,
+    /// Where the text always sits in a player's tree, so reloads and locale changes can swap
+    /// it in place.
+    pub path: Option<&'static str>,
+}
+
+impl Message {
+    pub const fn new(module: &'static str, id: &'static str) -> Message {
+        Message { module, id, path: None }
+    }
+
+    pub const fn at(self, path: &'static str) -> Message {
+        Message { path: Some(path), ..self }
+    }
+
+    pub fn text(self) -> String {
+        text_in(&locale(), self)
+    }
+
+    /// The text with every `{name}` replaced by its value.
+    pub fn format(self, values: &[(&str, &dyn Display)]) -> String {
+        values.iter().fold(self.text(), |text, (name, value)| text.replace(&format!("{{{name}}}"), &value.to_string()))
+    }
+}
+
+/// Every message the code uses.
+pub fn all() -> Vec<Message> {
+    [
+        start_mod::MESSAGES,
+        victory_file::MESSAGES,
+        progression::MESSAGES,
+        many_open::MESSAGES,
+        classroom::MESSAGES,
+        correct_order::MESSAGES,
+        kill_them_all::MESSAGES,
+        bathroom::MESSAGES,
+        find_the_suid::MESSAGES,
+        control::MESSAGES,
+        hint_file::MESSAGES,
+        progress_file::MESSAGES,
+        scoreboard::MESSAGES,
+    ].concat()
+}
+
+pub fn parse(text: &str) -> Result<Catalog, String> {
+    toml::from_str(text).map_err(|e| e.to_string())
+}
+
+/// The catalogs in `dir` as they are, by locale.
+fn read_dir(dir: &str) -> Result<BTreeMap<String, Catalog>, String> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Ok(BTreeMap::new());
+    };
+    let mut paths: Vec<_> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|x| x == "toml"))
+        .collect();
+    paths.sort();
+
+    let mut locales = BTreeMap::new();
+    for path in paths {
+        let locale = path.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+        let text = fs::read_to_string(&path).map_err(|e| format!("{}: {e}", path.display()))?;
+        locales.insert(locale, parse(&text).map_err(|e| format!("{}: {e}", path.display()))?);
+    }
+    Ok(locales)
+}
+
+/// Loads every `<locale>.toml` in `dir`. `en.toml` there goes over the English that is built in.
+pub fn load_dir(dir: &str) -> Result<BTreeMap<String, Catalog>, String> {
+    let mut locales = read_dir(dir)?;
+    let mut english = parse(ENGLISH).expect("Failed to parse the built in messages");
+    for (module, texts) in locales.remove(DEFAULT_LOCALE).unwrap_or_default() {
+        english.entry(module).or_default().extend(texts);
+    }
+    locales.insert(DEFAULT_LOCALE.to_string(), english);
+    Ok(locales)
+}
+
+pub fn install(locales: BTreeMap<String, Catalog>) {
+    CATALOGS.lock().unwrap().locales = locales;
+}
+
+/// The directory `reload` reads.
+pub fn set_dir(dir: &str) {
+    CATALOGS.lock().unwrap().dir = dir.to_string();
+}
+
+pub fn dir() -> String {
+    CATALOGS.lock().unwrap().dir.clone()
+}
+
+pub fn locales() -> Vec<String> {
+    CATALOGS.lock().unwrap().locales.keys().cloned().collect()
+}
+
+fn known(locale: &str) -> Result<(), String> {
+    if CATALOGS.lock().unwrap().locales.contains_key(locale) {
+        return Ok(());
+    }
+    Err(format!("no messages for {locale}, there are {}", locales().join(", ")))
+}
+
+/// The locale of players that did not pick one.
+pub fn set_default_locale(locale: &str) -> Result<(), String> {
+    known(locale)?;
+    CATALOGS.lock().unwrap().default = locale.to_string();
+    Ok(())
+}
+
+/// The current player's locale.
+pub fn locale() -> String {
+    progress::locale().unwrap_or_else(|| CATALOGS.lock().unwrap().default.clone())
+}
+
+fn lookup(locales: &BTreeMap<String, Catalog>, locale: &str, message: Message) -> Option<String> {
+    [locale, DEFAULT_LOCALE].iter()
+        .find_map(|locale| locales.get(*locale)?.get(message.module)?.get(message.id).cloned())
+}
+
+/// The text of `message` in `locale`, or in English if the locale has none.
+pub fn text_in(locale: &str, message: Message) -> String {
+    lookup(&CATALOGS.lock().unwrap().locales, locale, message).unwrap_or_else(|| {
+        warn!("no text for {}.{}", message.module, message.id);
+        format!("{}.{}\n", message.module, message.id)
+    })
+}
+
+/// Switches the current player to `locale`. Text in their rooms changes right away where it
+/// can, the rest when its room is built again.
+pub fn set_locale(locale: &str) -> Result<Pending, String> {
+    known(locale)?;
+    let old = self::locale();
+    progress::set_locale(locale);
+    let locales = CATALOGS.lock().unwrap().locales.clone();
+    Ok(swap(&locales, &old, &locales, locale))
+}
+
+/// Swaps the current player's text from the one in `old` to the one in `new`, in files that
+/// still show it.
+fn swap(old: &BTreeMap<String, Catalog>, old_locale: &str, new: &BTreeMap<String, Catalog>, new_locale: &str) -> Pending {
+    batch(|| {
+        for message in all() {
+            let Some(path) = message.path else {
+                continue;
+            };
+            if let (Some(old), Some(new)) = (lookup(old, old_locale, message), lookup(new, new_locale, message)) {
+                if old != new {
+                    replace_text(path, &old, &new);
+                }
+            }
+        }
+    })
+}
+
+/// Loads the catalogs in `dir` again, or changes nothing if one of them is broken. Changed text
+/// is swapped into the rooms of players that have not solved them yet.
+pub fn reload(dir: &str) -> Result<String, String> {
+    let locales = load_dir(dir)?;
+    let old = CATALOGS.lock().unwrap().locales.clone();
+    install(locales.clone());
+
+    let mut pending = Vec::new();
+    for player in players() {
+        with_player(player, || {
+            let locale = locale();
+            let unsolved: BTreeMap<String, Catalog> = locales.iter()
+                .map(|(name, catalog)| (name.clone(), catalog.iter().filter(|(module, _)| !progress::is_solved(module)).map(|(module, texts)| (module.clone(), texts.clone())).collect()))
+                .collect();
+            pending.push(swap(&old, &locale, &unsolved, &locale));
+        });
+    }
+    for pending in pending {
+        pending.wait()?;
+    }
+    Ok(format!("Reloaded {dir}\nLocales: {}", locales.keys().cloned().collect::<Vec<_>>().join(", ")))
+}
+
+/// Problems with the catalogs in `dir`, one per line: messages a locale leaves out and keys
+/// that no code uses. English comes from the binary if `dir` has none.
+pub fn check(dir: &str) -> Result<Vec<String>, String> {
+    let mut locales = read_dir(dir)?;
+    locales.entry(DEFAULT_LOCALE.to_string()).or_insert_with(|| parse(ENGLISH).expect("Failed to parse the built in messages"));
+
+    let used = all();
+    let mut problems = Vec::new();
+    for (locale, catalog) in &locales {
+        let fallback = if locale == DEFAULT_LOCALE { "" } else { ", shown in English" };
+        for message in &used {
+            if !catalog.get(message.module).is_some_and(|texts| texts.contains_key(message.id)) {
+                problems.push(format!("{locale}: missing {}.{}{fallback}", message.module, message.id));
+            }
+        }
+        for (module, texts) in catalog {
+            for id in texts.keys() {
+                if !used.iter().any(|message| message.module == module && message.id == id) {
+                    problems.push(format!("{locale}: orphaned {module}.{id}"));
+                }
+            }
+        }
+    }
+    Ok(problems)
+}
+
+/// `hack check-messages`: prints the problems and returns the exit code.
+pub fn check_command(dir: &Path) -> i32 {
+    match check(&dir.to_string_lossy()) {
+        Ok(problems) if problems.is_empty() => {
+            println!("{} messages, every locale has all of them", all().len());
+            0
+        }
+        Ok(problems) => {
+            for problem in problems {
+                println!("{problem}");
+            }
+            1
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            2
+        }
+    }
+}
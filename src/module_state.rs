@@ -0,0 +1,54 @@
+use std::{collections::{BTreeMap, HashMap}, sync::Mutex};
+
+use lazy_static::lazy_static;
+use serde_json::Value;
+
+use crate::{main_fs::{current_player, SHARED_PLAYER}, modules::registry, save};
+
+pub type PlayerState = HashMap<String, HashMap<String, Value>>;
+
+lazy_static! {
+    static ref STATE: Mutex<BTreeMap<u32, PlayerState>> = {
+        Mutex::new(BTreeMap::new())
+    };
+}
+
+/// Shared modules keep one state for everybody.
+fn owner(module: &str) -> u32 {
+    if registry::get(module).is_some_and(|module| module.shared) {
+        SHARED_PLAYER
+    } else {
+        current_player()
+    }
+}
+
+pub fn get(module: &str, key: &str) -> Option<Value> {
+    let owner = owner(module);
+    STATE.lock().unwrap().get(&owner).and_then(|state| state.get(module)).and_then(|values| values.get(key)).cloned()
+}
+
+pub fn get_u64(module: &str, key: &str, default: u64) -> u64 {
+    get(module, key).and_then(|value| value.as_u64()).unwrap_or(default)
+}
+
+pub fn set(module: &str, key: &str, value: Value) {
+    let owner = owner(module);
+    STATE.lock().unwrap().entry(owner).or_default().entry(module.to_string()).or_default().insert(key.to_string(), value);
+    save::save_later();
+}
+
+pub fn clear(module: &str) {
+    let owner = owner(module);
+    if let Some(state) = STATE.lock().unwrap().get_mut(&owner) {
+        state.remove(module);
+    }
+    save::save_later();
+}
+
+pub fn snapshot() -> BTreeMap<u32, PlayerState> {
+    STATE.lock().unwrap().clone()
+}
+
+pub fn restore(state: BTreeMap<u32, PlayerState>) {
+    *STATE.lock().unwrap() = state;
+}
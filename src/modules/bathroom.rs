@@ -1,34 +1,35 @@
 use std::{ffi::{c_int, OsStr, OsString}, str::FromStr, time::SystemTime};
 
 use fuser::FileAttr;
+use rand::Rng;
 
-use crate::{background_tasks::add_file, errors::PERMISSION_DENIED, file_helpers::{read, text_file}, files::File, main_fs::{GID, UID}};
+use crate::{background_tasks::add_file, errors::PERMISSION_DENIED, file_helpers::{read, text_file}, files::File, main_fs::{GID, UID}, messages::Message, random};
 
-const PLUMMER_MESSAGE: &str =
-"I have been trying to unclog this toilet for so long.
-Its basically impossible.
-";
+use super::registry;
 
-const POOP_MESSAGE: &str = 
-"$#@%!
-";
+const ID: &str = "bathroom";
 
-const TOILET_MESSAGE: &str = 
-"It's a toilet.
-WOW!
-";
+const PLUMBER: Message = Message::new(ID, "plumber").at("Bathroom/Plummer");
+const POOP: Message = Message::new(ID, "poop");
+const TOILET: Message = Message::new(ID, "toilet");
+pub const MESSAGES: &[Message] = &[PLUMBER, POOP, TOILET];
 
-const NUM_POOPS: usize = 250;
+const NUM_POOPS: [usize; 3] = [50, 250, 1000];
+
+const NUM_STALLS: usize = 4;
 
 pub fn start() {
-    add_file("Bathroom", text_file("Plummer", PLUMMER_MESSAGE));
-    add_file("Bathroom/stall_1", text_file("Toilet", TOILET_MESSAGE));
-    add_file("Bathroom/stall_2", text_file("Toilet", TOILET_MESSAGE));
-    add_file("Bathroom/stall_3", text_file("Toilet", TOILET_MESSAGE));
-    add_file("Bathroom/stall_4", text_file("Toilet", TOILET_MESSAGE));
+    add_file("Bathroom", text_file("Plummer", &PLUMBER.text()));
+    let toilet = TOILET.text();
+    for stall in 1..=NUM_STALLS {
+        add_file(&format!("Bathroom/stall_{stall}"), text_file("Toilet", &toilet));
+    }
 
-    for _ in 0..NUM_POOPS {
-        add_file("Bathroom/stall_3", text_file("SHIT", POOP_MESSAGE))
+    let clogged = random::rng(ID).gen_range(1..=NUM_STALLS);
+    let [easy, normal, hard] = NUM_POOPS;
+    let poop = POOP.text();
+    for _ in 0..registry::difficulty(ID).pick(easy, normal, hard) {
+        add_file(&format!("Bathroom/stall_{clogged}"), text_file("SHIT", &poop));
     }
 }
 
@@ -1,47 +1,45 @@
 use std::{ffi::{c_int, OsStr, OsString}, str::FromStr, time::SystemTime};
 
 use fuser::FileAttr;
+use log::trace;
 
-use crate::{background_tasks::{add_file, DEFAULT_MODE}, errors::{FILE_NOT_FOUND, PERMISSION_DENIED}, file_helpers::{read, str_to_vec, text_file, victory_file}, files::File, main_fs::{get_unique_ino, GID, UID}};
+use crate::{background_tasks::{add_file, DEFAULT_MODE}, difficulty::Difficulty, errors::{FILE_NOT_FOUND, PERMISSION_DENIED}, file_helpers::{read, str_to_vec, text_file}, files::File, hints::Hint, main_fs::{get_unique_ino, GID, UID}, messages::Message, progress};
 
-use super::many_open::ManyOpenFile;
+use super::registry;
 
+pub const ID: &str = "classroom";
+pub const BASE_PATH: &str = "Classroom";
 
-const TEACHER_MESSAGE: &str = 
-"I have been looking for Sally for the last 10 minutes but I can't find her.
-She was here earlier, but once I said we were studying math instead of reading and writing she started hiding.
-I guess she really likes reading and writing.
-";
 
-const SALLY_MESSAGE: &str = 
-"Oh we are going back to reading and writing!
-YEAYYY!
-Ill come back to class.
-";
+const TEACHER: Message = Message::new(ID, "teacher").at("Classroom/Teacher");
+const SALLY: Message = Message::new(ID, "sally");
+const BILLY: Message = Message::new(ID, "billy").at("Classroom/Billy");
+const TIMMY: Message = Message::new(ID, "timmy").at("Classroom/Timmy");
+const JOHN: Message = Message::new(ID, "john").at("Classroom/John");
+const HINT_1: Message = Message::new(ID, "hint_1");
+const HINT_2: Message = Message::new(ID, "hint_2");
+const HINT_3: Message = Message::new(ID, "hint_3");
+pub const MESSAGES: &[Message] = &[TEACHER, SALLY, BILLY, TIMMY, JOHN, HINT_1, HINT_2, HINT_3];
 
-const KID_1: &str =
-"Ok boomer!
-";
-
-const KID_2: &str =
-"I love Skibidi Toilet!
-";
-
-const KID_3: &str =
-"STOP HITTING ME BILLY!
-";
+pub fn hints() -> Vec<Hint> {
+    vec![
+        Hint::from_message(HINT_1, 180, 3),
+        Hint::from_message(HINT_2, 420, 6).up_to(Difficulty::Normal),
+        Hint::from_message(HINT_3, 600, 10).up_to(Difficulty::Easy),
+    ]
+}
 
 pub fn start() {
-    add_file("Classroom", Box::new(SallyFile::new(victory, "Sally", str_to_vec(SALLY_MESSAGE), get_unique_ino())));
-    add_file("Classroom", text_file("Teacher", TEACHER_MESSAGE));
+    add_file(BASE_PATH, Box::new(SallyFile::new(victory, "Sally", str_to_vec(&SALLY.text()), get_unique_ino())));
+    add_file(BASE_PATH, text_file("Teacher", &TEACHER.text()));
 
-    add_file("Classroom", text_file("Billy", KID_1));
-    add_file("Classroom", text_file("Timmy", KID_2));
-    add_file("Classroom", text_file("John", KID_3));
+    add_file(BASE_PATH, text_file("Billy", &BILLY.text()));
+    add_file(BASE_PATH, text_file("Timmy", &TIMMY.text()));
+    add_file(BASE_PATH, text_file("John", &JOHN.text()));
 }
 
 fn victory() {
-    add_file("Classroom", victory_file());
+    registry::victory(ID);
 }
 
 
@@ -125,10 +123,11 @@ impl File for SallyFile {
     }
 
     fn open(&mut self, flags: i32) -> Result<u32, c_int> {
-        println!("{:x} {} {}", flags, flags & libc::O_RDONLY, flags & libc::O_RDWR);
+        trace!(flags = flags; "teacher opened");
         if flags & libc::O_RDWR != 0 {
             Ok(0)
         } else {
+            progress::attempt(ID);
             Err(FILE_NOT_FOUND)
         }
     }
@@ -1,49 +1,89 @@
-use std::{cell::RefCell, ffi::{c_int, OsStr, OsString}, str::FromStr, sync::Mutex, time::SystemTime};
+use std::{ffi::{c_int, OsStr, OsString}, str::FromStr, time::{Duration, SystemTime}};
 
 use fuser::FileAttr;
-use lazy_static::lazy_static;
+use rand::seq::SliceRandom;
 
-const BASE_PATH: &str = "Broken_Sorter";
+pub const ID: &str = "correct_order";
+pub const BASE_PATH: &str = "Broken_Sorter";
 
-use crate::{background_tasks::{add_file, DEFAULT_MODE}, errors::PERMISSION_DENIED, file_helpers::{read, victory_file}, files::File, main_fs::{get_unique_ino, GID, UID}};
+use crate::{background_tasks::{add_file, DEFAULT_MODE}, difficulty::Difficulty, errors::PERMISSION_DENIED, file_helpers::read, files::File, hints::Hint, main_fs::{get_unique_ino, GID, UID}, messages::Message, module_state, progress, random, scheduler};
 
+use super::registry;
 
-const ORDER: [usize; 8] = [3, 5, 4, 1, 2, 7, 6, 99999999];
+/// Files in the sorter. All but one of them have to be read, the last one is never right.
+const NUM_FILES: [usize; 3] = [5, 8, 12];
+/// On hard the whole order has to be read this soon after the first right file.
+pub const TIME_LIMIT: Duration = Duration::from_secs(60);
 
-lazy_static! {
-    static ref POS: Mutex<usize> = {
-        Mutex::new(0)
-    };
+/// Both answers have the same length in every language, or the size would give them away.
+const RIGHT: Message = Message::new(ID, "right");
+const WRONG: Message = Message::new(ID, "wrong");
+const HINT_1: Message = Message::new(ID, "hint_1");
+const HINT_2: Message = Message::new(ID, "hint_2");
+const HINT_3: Message = Message::new(ID, "hint_3");
+pub const MESSAGES: &[Message] = &[RIGHT, WRONG, HINT_1, HINT_2, HINT_3];
+
+pub fn hints() -> Vec<Hint> {
+    vec![
+        Hint::from_message(HINT_1, 180, 5),
+        Hint::from_message(HINT_2, 420, 15).up_to(Difficulty::Normal),
+        Hint::from_message(HINT_3, 600, 25).up_to(Difficulty::Easy),
+    ]
 }
 
-const WRONG_MESSAGE: &str = "No \n";
-const RIGHT_MESSAGE: &str = "Yes\n";
+fn num_files() -> usize {
+    let [easy, normal, hard] = NUM_FILES;
+    registry::difficulty(ID).pick(easy, normal, hard)
+}
 
 pub fn start() {
-    for i in 0..8 {
+    for i in 0..num_files() {
         add_file(BASE_PATH, Box::new(OrderFile::new(i)));
     }
 }
 
+/// The files in the order the current player has to read them.
+pub fn order() -> Vec<usize> {
+    let mut order: Vec<usize> = (0..num_files()).collect();
+    order.shuffle(&mut random::rng(ID));
+    order.pop();
+    order
+}
+
 fn order_trigger(file_num: usize) -> bool {
-    let mut pos = POS.lock().unwrap();
-    if ORDER[*pos] == file_num {
-        *pos += 1;
-        if *pos >= ORDER.len() - 1 {
-            add_file(BASE_PATH, victory_file());
+    let pos = module_state::get_u64(ID, "pos", 0) as usize;
+    let order = order();
+    if order.get(pos) == Some(&file_num) {
+        module_state::set(ID, "pos", (pos + 1).into());
+        if pos + 1 >= order.len() {
+            scheduler::cancel_module(ID);
+            registry::victory(ID);
+        } else if pos == 0 && registry::difficulty(ID) == Difficulty::Hard {
+            scheduler::after(ID, TIME_LIMIT, time_up);
         }
         true
     } else {
-        *pos = 0;
+        // The time limit is the only job of the sorter.
+        scheduler::cancel_module(ID);
+        module_state::set(ID, "pos", 0.into());
+        progress::attempt(ID);
         false
     }
 }
 
+/// Too slow, the sorter starts over.
+fn time_up() {
+    module_state::set(ID, "pos", 0.into());
+    progress::attempt(ID);
+}
+
 #[derive(Debug)]
 pub struct OrderFile {
     pub attr: FileAttr,
     pub name: OsString,
     pub file_num: usize,
+    /// What the last read got.
+    pub answer: Vec<u8>,
 }
 
 impl OrderFile {
@@ -51,7 +91,7 @@ impl OrderFile {
         Self {
             attr: FileAttr { 
                 ino: get_unique_ino(), 
-                size: 4, 
+                size: RIGHT.text().len() as u64, 
                 blocks: 0, 
                 atime: SystemTime::now(), 
                 mtime: SystemTime::now(), 
@@ -68,6 +108,7 @@ impl OrderFile {
             },
             name: OsString::from_str(&format!("{file_num}")).unwrap(),
             file_num,
+            answer: Vec::new(),
         }
     }
 }
@@ -98,12 +139,8 @@ impl File for OrderFile {
     }
     
     fn read(&mut self, offset: i64, size: u32, flags: i32) -> Result<&[u8], c_int> {
-        let data = if order_trigger(self.file_num) {
-            RIGHT_MESSAGE.as_bytes()
-        } else {
-            WRONG_MESSAGE.as_bytes()
-        };
-        read(data, offset, size)
+        self.answer = if order_trigger(self.file_num) { RIGHT } else { WRONG }.text().into_bytes();
+        read(&self.answer, offset, size)
     }
     
     fn write(&mut self, offset: i64, data: &[u8], write_flags: u32, flags: i32) -> Result<u32, c_int> {
@@ -1,31 +1,36 @@
 use std::process::Command;
 
-use crate::{background_tasks::{add_file, DEFAULT_MODE}, file_helpers::text_file, main_fs::get_unique_ino, special_files::trigger_file::TriggerFile, MOUNT_POINT};
+use rand::Rng;
 
+use crate::{background_tasks::{add_file, DEFAULT_MODE}, file_helpers::text_file, main_fs::get_unique_ino, messages::Message, special_files::trigger_file::TriggerFile, cli::mount_point, random};
+
+use super::registry;
+
+const ID: &str = "find_the_suid";
 const BASE_PATH: &str = "Where's_Waldo";
 const FILE_NAME: &str = "Waldo";
-const FAKE_MESSAGE: &str = 
-"Hi I'm Waldo.
-";
-
-const REAL_MESSAGE: &str =
-"No I am the real Waldo.
-";
+const FAKE: Message = Message::new(ID, "fake");
+const REAL: Message = Message::new(ID, "real");
+pub const MESSAGES: &[Message] = &[FAKE, REAL];
 
-const NUM_WALDOS_PART_1: usize = 60;
-const NUM_WALDOS_PART_2: usize = 33;
+/// Fake Waldos hiding the real one.
+const NUM_WALDOS: [usize; 3] = [20, 93, 300];
 
 pub fn start() {
-    add_file(BASE_PATH, Box::new(TriggerFile::new(found_trigger, FILE_NAME, REAL_MESSAGE.as_bytes().iter().map(|x| *x).collect(), get_unique_ino(), DEFAULT_MODE | libc::S_ISUID, 0)));
-    let file_path = format!("{MOUNT_POINT}/{BASE_PATH}/{FILE_NAME}");
+    let file_path = format!("{}/{BASE_PATH}/{FILE_NAME}", mount_point().display());
     Command::new("chmod").arg("+s").arg("arg").output().expect("Failed to run command");
 
-    for _ in 0..NUM_WALDOS_PART_2 {
-        add_file(BASE_PATH, text_file(FILE_NAME, FAKE_MESSAGE));
-    }
-
-    for _ in 0..NUM_WALDOS_PART_1 {
-        add_file(BASE_PATH, text_file(FILE_NAME, FAKE_MESSAGE));
+    // The real Waldo hides somewhere among the fakes, in a different spot for every player.
+    let [easy, normal, hard] = NUM_WALDOS;
+    let fakes = registry::difficulty(ID).pick(easy, normal, hard);
+    let real = random::rng(ID).gen_range(0..=fakes);
+    let fake = FAKE.text();
+    for i in 0..=fakes {
+        if i == real {
+            add_file(BASE_PATH, Box::new(TriggerFile::new(found_trigger, FILE_NAME, REAL.text().into_bytes(), get_unique_ino(), DEFAULT_MODE | libc::S_ISUID, 0)));
+        } else {
+            add_file(BASE_PATH, text_file(FILE_NAME, &fake));
+        }
     }
 }
 
@@ -1,45 +1,75 @@
-use std::{ffi::{c_int, OsStr, OsString}, process::Command, str::FromStr, sync::Mutex, time::SystemTime};
+use std::{ffi::{c_int, OsStr, OsString}, ops::RangeInclusive, process::Command, str::FromStr, time::{Duration, SystemTime}};
 
 use fuser::FileAttr;
-use lazy_static::lazy_static;
+use log::debug;
+use rand::Rng;
 
-use crate::{background_tasks::{add_file, DEFAULT_MODE}, errors::PERMISSION_DENIED, file_helpers::{read, text_file, victory_file}, files::File, main_fs::{get_unique_ino, GID, UID}, special_files::trigger_file::TriggerFile, MOUNT_POINT};
+use crate::{background_tasks::{add_file, DEFAULT_MODE}, difficulty::Difficulty, errors::PERMISSION_DENIED, file_helpers::{read, text_file}, files::File, hints::Hint, main_fs::{get_unique_ino, GID, UID}, messages::Message, module_state, random, scheduler, cli::mount_point};
 
-const BASE_PATH: &str = "Arena";
+use super::registry;
+
+pub const ID: &str = "kill_them_all";
+pub const BASE_PATH: &str = "Arena";
 const FILE_NAME: &str = "Ogre";
-const OGRE_MESSAGE: &str = 
-"He is scary!
-";
-
-const WARRIOR_MESSAGE: &str = 
-"Ah adventurer!
-Help me kill these Ogres.
-";
-
-lazy_static! {
-    static ref OGRES_LEFT: Mutex<usize> = {
-        Mutex::new(NUM_OGRES)
-    };
+const OGRE: Message = Message::new(ID, "ogre");
+const WARRIOR: Message = Message::new(ID, "warrior").at("Arena/Warrior");
+const HINT_1: Message = Message::new(ID, "hint_1");
+const HINT_2: Message = Message::new(ID, "hint_2");
+const HINT_3: Message = Message::new(ID, "hint_3");
+pub const MESSAGES: &[Message] = &[OGRE, WARRIOR, HINT_1, HINT_2, HINT_3];
+
+/// How many Ogres there are, drawn per player.
+const OGRES: [RangeInclusive<u64>; 3] = [5..=10, 15..=25, 40..=60];
+/// On hard a new Ogre shows up this often until they are all dead.
+pub const RESPAWN: Duration = Duration::from_secs(15);
+
+pub fn hints() -> Vec<Hint> {
+    vec![
+        Hint::from_message(HINT_1, 180, 3),
+        Hint::from_message(HINT_2, 420, 6).up_to(Difficulty::Normal),
+        Hint::from_message(HINT_3, 600, 10).up_to(Difficulty::Easy),
+    ]
 }
 
-const NUM_OGRES: usize = 20;
-
 pub fn start() {
-    let file_path = format!("{MOUNT_POINT}/{BASE_PATH}/{FILE_NAME}");
+    let file_path = format!("{}/{BASE_PATH}/{FILE_NAME}", mount_point().display());
     Command::new("chmod").arg("+s").arg("arg").output().expect("Failed to run command");
 
-    add_file(BASE_PATH, text_file("Warrior", WARRIOR_MESSAGE));
+    add_file(BASE_PATH, text_file("Warrior", &WARRIOR.text()));
 
-    for _ in 0..NUM_OGRES {
-        add_file(BASE_PATH, Box::new(OgreFile::new(killed_ogre, FILE_NAME, OGRE_MESSAGE.as_bytes().iter().map(|x| *x).collect(), get_unique_ino(), DEFAULT_MODE, 0)));
+    let ogres_left = module_state::get_u64(ID, "ogres_left", num_ogres());
+    for _ in 0..ogres_left {
+        add_ogre();
+    }
+    if ogres_left > 0 && registry::difficulty(ID) == Difficulty::Hard {
+        scheduler::every(ID, RESPAWN, respawn);
     }
 }
 
+fn add_ogre() {
+    add_file(BASE_PATH, Box::new(OgreFile::new(killed_ogre, FILE_NAME, OGRE.text().into_bytes(), get_unique_ino(), DEFAULT_MODE, 0)));
+}
+
+pub fn num_ogres() -> u64 {
+    let [easy, normal, hard] = OGRES;
+    random::rng(ID).gen_range(registry::difficulty(ID).pick(easy, normal, hard))
+}
+
+fn respawn() {
+    let ogres_left = module_state::get_u64(ID, "ogres_left", num_ogres());
+    module_state::set(ID, "ogres_left", (ogres_left + 1).into());
+    debug!(ogres_left = ogres_left + 1; "ogre respawned");
+    add_ogre();
+}
+
 fn killed_ogre() {
-    *OGRES_LEFT.lock().unwrap() -= 1;
+    let ogres_left = module_state::get_u64(ID, "ogres_left", num_ogres()).saturating_sub(1);
+    module_state::set(ID, "ogres_left", ogres_left.into());
+    debug!(ogres_left = ogres_left; "ogre killed");
 
-    if *OGRES_LEFT.lock().unwrap() <= 0 {
-        add_file(BASE_PATH, victory_file());
+    if ogres_left == 0 {
+        scheduler::cancel_module(ID);
+        registry::victory(ID);
     }
 
 }
@@ -116,7 +146,6 @@ impl File for OgreFile {
     }
     
     fn delete(&mut self) -> Result<(), c_int> {
-        println!("Delete");
         (self.trigger)();
         Ok(())
     }
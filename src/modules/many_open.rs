@@ -1,26 +1,46 @@
-use std::{ffi::{c_int, OsStr, OsString}, str::FromStr, time::SystemTime};
+use std::{ffi::{c_int, OsStr, OsString}, ops::RangeInclusive, str::FromStr, time::SystemTime};
 
 use fuser::FileAttr;
+use log::trace;
+use rand::Rng;
 
-use crate::{background_tasks::{add_file, DEFAULT_MODE}, errors::PERMISSION_DENIED, file_helpers::{read, str_to_vec, victory_file}, files::File, main_fs::{get_unique_ino, GID, UID}};
+use crate::{background_tasks::{add_file, DEFAULT_MODE}, difficulty::Difficulty, errors::PERMISSION_DENIED, file_helpers::{read, str_to_vec}, files::File, hints::Hint, main_fs::{get_unique_ino, GID, UID}, messages::Message, progress, random};
 
-pub fn start() {
-    add_file("The_Door", Box::new(ManyOpenFile::new(victory, "Heavy_Door", get_unique_ino())));
+use super::registry;
+
+pub const ID: &str = "many_open";
+pub const BASE_PATH: &str = "The_Door";
+
+pub fn hints() -> Vec<Hint> {
+    vec![
+        Hint::from_message(HINT_1, 180, 3),
+        Hint::from_message(HINT_2, 420, 8).up_to(Difficulty::Normal),
+        Hint::from_message(HINT_3, 600, 12).up_to(Difficulty::Easy),
+    ]
 }
 
-pub fn victory() {
-    add_file("The_Door", victory_file())
+/// How many handles the door wants at the same time, drawn per player.
+const OPENS: [RangeInclusive<u32>; 3] = [3..=5, 6..=14, 25..=40];
+
+const CLOSED: Message = Message::new(ID, "closed");
+const OPEN: Message = Message::new(ID, "open");
+const HINT_1: Message = Message::new(ID, "hint_1");
+const HINT_2: Message = Message::new(ID, "hint_2");
+const HINT_3: Message = Message::new(ID, "hint_3");
+pub const MESSAGES: &[Message] = &[CLOSED, OPEN, HINT_1, HINT_2, HINT_3];
+
+pub fn start() {
+    add_file(BASE_PATH, Box::new(ManyOpenFile::new(victory, "Heavy_Door", get_unique_ino(), opens_needed())));
 }
 
-const MESSAGE_1: &str = 
-"You think you can get past me that easy.
-You would need 10 men to try and open me to even have a chance.
-";
+pub fn opens_needed() -> u32 {
+    let [easy, normal, hard] = OPENS;
+    random::rng(ID).gen_range(registry::difficulty(ID).pick(easy, normal, hard))
+}
 
-const MESSAGE_2: &str = 
-"Wow you actually got 10 men.
-The door is now open and you have beaten this module.
-";
+pub fn victory() {
+    registry::victory(ID)
+}
 
 #[derive(Debug)]
 pub struct ManyOpenFile {
@@ -31,11 +51,12 @@ pub struct ManyOpenFile {
     pub trigger: fn() -> (),
     pub data2: Vec<u8>,
     pub triggered: bool,
+    pub needed: u32,
 }
 
 impl ManyOpenFile {
-    pub fn new(trigger: fn() -> (), name: &str, ino: u64) -> Self {
-        let data = str_to_vec(MESSAGE_1);
+    pub fn new(trigger: fn() -> (), name: &str, ino: u64, needed: u32) -> Self {
+        let data = str_to_vec(&CLOSED.format(&[("opens", &needed)]));
         Self {
             attr: FileAttr { 
                 ino, 
@@ -56,10 +77,11 @@ impl ManyOpenFile {
             },
             name: OsString::from_str(name).unwrap(),
             data,
-            data2: str_to_vec(MESSAGE_2),
+            data2: str_to_vec(&OPEN.format(&[("opens", &needed)])),
             trigger,
             num_opens: 0,
             triggered: false,
+            needed,
         }
     }
 }
@@ -90,15 +112,16 @@ impl File for ManyOpenFile {
     }
     
     fn read(&mut self, offset: i64, size: u32, flags: i32) -> Result<&[u8], c_int> {
-        let data = if self.num_opens >= 10 {
+        let data = if self.num_opens >= self.needed {
             if !self.triggered {
                 (self.trigger)();
                 self.triggered = true;
             }
-            println!("Data 2");
             &self.data2
         } else {
-            println!("Data 1");
+            if offset == 0 {
+                progress::attempt(ID);
+            }
 
             &self.data
         };
@@ -117,13 +140,13 @@ impl File for ManyOpenFile {
 
     fn open(&mut self, flags: i32) -> Result<u32, c_int> {
         self.num_opens += 1;
-        println!("Open: {}", self.num_opens);
+        trace!(opens = self.num_opens; "door opened");
         Ok(flags as u32)
     }
 
     fn release(&mut self) -> Result<(), c_int> {
         self.num_opens -= 1;
-        println!("Close: {}", self.num_opens);
+        trace!(opens = self.num_opens; "door closed");
         Ok(())
     }
 }
\ No newline at end of file
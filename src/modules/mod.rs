@@ -3,5 +3,7 @@ pub mod many_open;
 pub mod classroom;
 pub mod correct_order;
 pub mod bathroom;
-mod find_the_suid;
+pub mod find_the_suid;
+pub mod registry;
+pub mod progression;
 pub mod kill_them_all;
\ No newline at end of file
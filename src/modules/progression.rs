@@ -0,0 +1,124 @@
+use std::collections::HashSet;
+
+use crate::{background_tasks::{add_file, batch, rm_dir, Pending}, file_helpers::text_file, messages::Message, progress};
+
+use super::registry::{self, Module};
+
+/// The only file in the directory of a module that is still locked.
+pub const LOCKED_FILE: &str = "Locked";
+/// Stands for every other module in `requires`, for a final room.
+pub const EVERY_MODULE: &str = "*";
+
+const LOCKED: Message = Message::new("progression", "locked");
+const AND: Message = Message::new("progression", "and");
+pub const MESSAGES: &[Message] = &[LOCKED, AND];
+
+/// Adds requirements like `Classroom=The_Door` from the command line, by id or directory
+/// name, then checks every module's requirements.
+pub fn set_requirements(settings: &[String]) -> Result<(), String> {
+    for setting in settings {
+        let (name, required) = setting.split_once('=').ok_or_else(|| format!("{setting} is not <module>=<required module>"))?;
+        let module = registry::find(name).ok_or_else(|| format!("unknown module {name}"))?;
+        registry::add_requirement(&module.id, required);
+    }
+    check()
+}
+
+/// Every required module has to be selected, and nothing may end up requiring itself.
+pub fn check() -> Result<(), String> {
+    check_modules(&registry::modules())
+}
+
+pub fn check_modules(modules: &[Module]) -> Result<(), String> {
+    for module in modules {
+        for name in &module.requires {
+            if name != EVERY_MODULE && !modules.iter().any(|other| other.id == *name || other.dir == *name) {
+                return Err(format!("{} requires {name}, which is not a selected module", module.dir));
+            }
+            if *name == module.id || *name == module.dir {
+                return Err(format!("{} requires itself", module.dir));
+            }
+        }
+    }
+
+    fn visit(module: &Module, modules: &[Module], path: &mut Vec<String>, done: &mut HashSet<String>) -> Result<(), String> {
+        if done.contains(&module.id) {
+            return Ok(());
+        }
+        if path.contains(&module.dir) {
+            path.push(module.dir.clone());
+            return Err(format!("modules require each other: {}", path.join(" -> ")));
+        }
+        path.push(module.dir.clone());
+        for required in requirements(module, modules) {
+            visit(&required, modules, path, done)?;
+        }
+        path.pop();
+        done.insert(module.id.clone());
+        Ok(())
+    }
+    let mut done = HashSet::new();
+    for module in modules {
+        visit(module, modules, &mut Vec::new(), &mut done)?;
+    }
+    Ok(())
+}
+
+/// The modules `module` needs solved first.
+fn requirements(module: &Module, modules: &[Module]) -> Vec<Module> {
+    let every = |module: &Module| module.requires.iter().any(|name| name == EVERY_MODULE);
+    modules.iter()
+        .filter(|other| other.id != module.id)
+        .filter(|other| if every(module) { !every(other) } else { module.requires.iter().any(|name| *name == other.id || *name == other.dir) })
+        .cloned()
+        .collect()
+}
+
+/// Required modules the current player has not solved yet.
+pub fn missing(module: &Module) -> Vec<Module> {
+    requirements(module, &registry::modules()).into_iter().filter(|required| !progress::is_solved(&required.id)).collect()
+}
+
+pub fn is_started(module: &Module) -> bool {
+    progress::snapshot().modules.get(&module.id).is_some_and(|x| x.started.is_some())
+}
+
+/// Builds the module for the current player if they may play it, or puts up its locked door.
+/// Modules that were started once stay open.
+pub fn show(module: &Module) {
+    let missing = missing(module);
+    if missing.is_empty() || is_started(module) {
+        registry::start(module);
+        return;
+    }
+
+    let names: Vec<String> = missing.iter().map(|x| x.dir.clone()).collect();
+    let text = LOCKED.format(&[("modules", &join(&names))]);
+    add_file(&module.dir, text_file(LOCKED_FILE, &text));
+}
+
+/// Opens every locked module whose requirements the current player just finished.
+pub fn unlock_ready() {
+    for module in registry::modules() {
+        if !is_started(&module) && !module.requires.is_empty() && missing(&module).is_empty() {
+            unlock(&module);
+        }
+    }
+}
+
+/// Swaps the locked door for the module, whatever is still missing.
+pub fn unlock(module: &Module) -> Pending {
+    batch(|| {
+        rm_dir(&module.dir);
+        registry::start(module);
+    })
+}
+
+/// `a`, `a and b`, `a, b and c`, in the player's language.
+fn join(names: &[String]) -> String {
+    match names {
+        [] => String::new(),
+        [name] => name.clone(),
+        [rest @ .., last] => format!("{} {} {last}", rest.join(", "), AND.text().trim()),
+    }
+}
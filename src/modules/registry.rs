@@ -0,0 +1,252 @@
+use std::{collections::{HashMap, HashSet}, sync::Mutex, thread};
+
+use lazy_static::lazy_static;
+use log::warn;
+
+use crate::{background_tasks::{add_file, batch, link_shared_dir, rm_dir}, difficulty::Difficulty, file_helpers::victory_file, hints::{self, Hint}, main_fs::{current_player, players, with_player, SHARED_PLAYER}, module_state, progress::{self, Intro}, scheduler, special_files::trigger_file::Trigger};
+
+use super::{classroom, correct_order, kill_them_all, many_open, progression};
+
+lazy_static! {
+    static ref MODULES: Mutex<Vec<Module>> = {
+        Mutex::new(Vec::new())
+    };
+    static ref SHARED_BUILT: Mutex<HashSet<String>> = {
+        Mutex::new(HashSet::new())
+    };
+    static ref DIFFICULTY: Mutex<Difficulties> = {
+        Mutex::new(Difficulties::default())
+    };
+}
+
+/// What the instructor chose, for every module and for single ones.
+#[derive(Debug, Default)]
+struct Difficulties {
+    all: Difficulty,
+    modules: HashMap<String, Difficulty>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Module {
+    pub id: String,
+    pub dir: String,
+    pub start: Trigger,
+    pub hints: Vec<Hint>,
+    /// Built once and linked into every player's root, with one state for everybody.
+    /// Other modules get a copy per player.
+    pub shared: bool,
+    /// Modules, by id or directory name, that have to be solved before this one opens.
+    /// See `progression`.
+    pub requires: Vec<String>,
+}
+
+impl Module {
+    /// A module without hints, built per player and open from the start.
+    pub fn new(id: &str, dir: &str, start: impl Into<Trigger>) -> Self {
+        Module { id: id.to_string(), dir: dir.to_string(), start: start.into(), hints: Vec::new(), shared: false, requires: Vec::new() }
+    }
+
+    pub fn hints(mut self, hints: Vec<Hint>) -> Self {
+        self.hints = hints;
+        self
+    }
+
+    pub fn shared(mut self, shared: bool) -> Self {
+        self.shared = shared;
+        self
+    }
+
+    pub fn requires(mut self, requires: Vec<String>) -> Self {
+        self.requires = requires;
+        self
+    }
+}
+
+/// Adds `module`, or replaces the one with the same id.
+pub fn register(module: Module) {
+    let mut modules = MODULES.lock().unwrap();
+    match modules.iter_mut().find(|old| old.id == module.id) {
+        Some(old) => *old = module,
+        None => modules.push(module),
+    }
+}
+
+pub fn register_builtin() {
+    register(Module::new(many_open::ID, many_open::BASE_PATH, many_open::start).hints(many_open::hints()));
+    register(Module::new(classroom::ID, classroom::BASE_PATH, classroom::start).hints(classroom::hints()));
+    register(Module::new(correct_order::ID, correct_order::BASE_PATH, correct_order::start).hints(correct_order::hints()));
+    //register(Module::new("bathroom", bathroom::BASE_PATH, bathroom::start));
+    register(Module::new(kill_them_all::ID, kill_them_all::BASE_PATH, kill_them_all::start).hints(kill_them_all::hints()));
+}
+
+pub fn set_requirements(id: &str, requires: Vec<String>) {
+    if let Some(module) = MODULES.lock().unwrap().iter_mut().find(|module| module.id == id) {
+        module.requires = requires;
+    }
+}
+
+pub fn add_requirement(id: &str, required: &str) {
+    if let Some(module) = MODULES.lock().unwrap().iter_mut().find(|module| module.id == id) {
+        module.requires.push(required.to_string());
+    }
+}
+
+/// Keeps only the chosen modules, or all of them when `only` is empty, minus `exclude`.
+/// Both take ids or directory names.
+pub fn select(only: &[String], exclude: &[String]) -> Result<(), String> {
+    let mut modules = MODULES.lock().unwrap();
+    for name in only.iter().chain(exclude) {
+        if !modules.iter().any(|module| &module.id == name || &module.dir == name) {
+            return Err(format!("unknown module {name}"));
+        }
+    }
+    let matches = |module: &Module, names: &[String]| names.iter().any(|name| name == &module.id || name == &module.dir);
+    modules.retain(|module| (only.is_empty() || matches(module, only)) && !matches(module, exclude));
+    Ok(())
+}
+
+/// Takes settings like `hard` for every module or `Arena=easy` for one, by id or directory name.
+pub fn set_difficulty(settings: &[String]) -> Result<(), String> {
+    let mut difficulties = DIFFICULTY.lock().unwrap();
+    for setting in settings {
+        match setting.split_once('=') {
+            Some((name, difficulty)) => {
+                let module = find(name).ok_or_else(|| format!("unknown module {name}"))?;
+                difficulties.modules.insert(module.id, difficulty.parse()?);
+            }
+            None => difficulties.all = setting.parse()?,
+        }
+    }
+    Ok(())
+}
+
+/// The difficulty new players get for the module.
+pub fn configured_difficulty(id: &str) -> Difficulty {
+    let difficulties = DIFFICULTY.lock().unwrap();
+    difficulties.modules.get(id).copied().unwrap_or(difficulties.all)
+}
+
+/// The difficulty the current player's copy of the module was built with.
+pub fn difficulty(id: &str) -> Difficulty {
+    progress::snapshot().modules.get(id)
+        .and_then(|x| x.difficulty)
+        .unwrap_or_else(|| configured_difficulty(id))
+}
+
+/// The module's hints at the current player's difficulty.
+pub fn hints(module: &Module) -> Vec<Hint> {
+    let difficulty = difficulty(&module.id);
+    module.hints.iter().filter(|hint| hint.max_difficulty.is_none_or(|max| difficulty <= max)).cloned().collect()
+}
+
+pub fn modules() -> Vec<Module> {
+    MODULES.lock().unwrap().clone()
+}
+
+pub fn get(id: &str) -> Option<Module> {
+    MODULES.lock().unwrap().iter().find(|module| module.id == id).cloned()
+}
+
+/// Looks a module up by id or by directory name.
+pub fn find(name: &str) -> Option<Module> {
+    MODULES.lock().unwrap().iter().find(|module| module.id == name || module.dir == name).cloned()
+}
+
+/// Builds every module the current player may play and locks the others.
+pub fn start_all() {
+    for module in modules() {
+        progression::show(&module);
+    }
+}
+
+/// Builds the module for the current player and, when restoring a save, hands back its `Victory` file.
+/// The whole room shows up at once.
+pub fn start(module: &Module) {
+    batch(|| build(module));
+}
+
+fn build(module: &Module) {
+    progress::started(&module.id, configured_difficulty(&module.id));
+    if module.shared {
+        if SHARED_BUILT.lock().unwrap().insert(module.id.clone()) {
+            with_player(SHARED_PLAYER, || {
+                module.start.fire();
+                if progress::is_solved(&module.id) {
+                    add_file(&module.dir, victory_file(&module.id));
+                }
+            });
+        }
+        link_shared_dir(&module.dir, SHARED_PLAYER);
+    } else {
+        module.start.fire();
+        if progress::is_solved(&module.id) {
+            add_file(&module.dir, victory_file(&module.id));
+        }
+    }
+    if progress::snapshot().modules.get(&module.id).is_some_and(|x| x.hints > 0) {
+        hints::add_hint_file(module);
+    }
+}
+
+/// Tears the module's directory down, forgets its state and progress and builds it again.
+pub fn reset(name: &str) -> Result<Module, String> {
+    let module = find(name).ok_or_else(|| format!("unknown module {name}"))?;
+    let started = progress::snapshot().modules.get(&module.id).is_some_and(|x| x.started.is_some());
+
+    scheduler::cancel_module(&module.id);
+    module_state::clear(&module.id);
+    progress::reset(&module.id);
+    let reset_module = module.clone();
+    let resetter = current_player();
+    thread::spawn(move || batch(|| {
+        if !reset_module.shared {
+            with_player(resetter, || {
+                rm_dir(&reset_module.dir);
+                if started {
+                    start(&reset_module);
+                } else if progress::intro() == Intro::Done {
+                    progression::show(&reset_module);
+                }
+            });
+            return;
+        }
+
+        // Everyone loses the shared room, so rebuild it for everyone that had it.
+        with_player(SHARED_PLAYER, || {
+            scheduler::cancel_module(&reset_module.id);
+            progress::reset(&reset_module.id);
+            rm_dir(&reset_module.dir);
+        });
+        SHARED_BUILT.lock().unwrap().remove(&reset_module.id);
+        for player in players() {
+            with_player(player, || {
+                let had_it = progress::snapshot().modules.get(&reset_module.id).is_some_and(|x| x.started.is_some());
+                if had_it || (player == resetter && started) {
+                    start(&reset_module);
+                }
+            });
+        }
+    }));
+    Ok(module)
+}
+
+/// Marks the module as solved and adds its `Victory` file, once.
+pub fn victory(id: &str) {
+    let Some(module) = get(id) else {
+        warn!("victory for unknown module {id}");
+        return;
+    };
+    if !progress::solve(id) {
+        return;
+    }
+    if module.shared {
+        with_player(SHARED_PLAYER, || {
+            if progress::solve(id) {
+                add_file(&module.dir, victory_file(&module.id));
+            }
+        });
+    } else {
+        add_file(&module.dir, victory_file(&module.id));
+    }
+    progression::unlock_ready();
+}
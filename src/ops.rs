@@ -0,0 +1,256 @@
+use std::{collections::HashMap, ffi::{c_int, OsStr, OsString}, os::unix::ffi::OsStrExt, path::{Component, Path}, sync::Mutex};
+
+use fuser::{FileAttr, FileType, FUSE_ROOT_ID};
+use lazy_static::lazy_static;
+use log::warn;
+
+use crate::{errors::{FILE_NOT_FOUND, NOT_SUPPORTED, PERMISSION_DENIED}, main_fs::{current_player, current_request, get_unique_ino, lookup as lookup_attr, FsData, Ino, DATA, GID, UID}, user_files::{NormalDir, UserFile}};
+
+lazy_static! {
+    /// Paths the audit log already knows, by player and ino.
+    static ref PATHS: Mutex<HashMap<(u32, u64), String>> = {
+        Mutex::new(HashMap::new())
+    };
+}
+
+// The filesystem operations without the kernel around them. `MainFs` replies with
+// whatever these return, the replay tool calls them directly. Like the kernel they
+// take inos, where ino 1 is the root of the current player.
+
+fn request_owner() -> (u32, u32) {
+    current_request().map(|req| (req.uid, req.gid)).unwrap_or((*UID, *GID))
+}
+
+pub fn lookup(parent: u64, name: &OsStr) -> Result<FileAttr, c_int> {
+    let mut data = DATA.lock().unwrap();
+    let parent = data.resolve(parent);
+    if !data.inos.contains_key(&parent) {
+        return Err(FILE_NOT_FOUND);
+    }
+    lookup_attr(parent, name, &data).copied()
+}
+
+pub fn getattr(ino: u64) -> Result<FileAttr, c_int> {
+    let mut data = DATA.lock().unwrap();
+    let resolved = data.resolve(ino);
+    let mut attr = *data.inos.get(&resolved).ok_or(FILE_NOT_FOUND)?.attr();
+    attr.ino = ino;
+    Ok(attr)
+}
+
+pub fn setattr(
+    ino: u64,
+    mode: Option<u32>,
+    uid: Option<u32>,
+    gid: Option<u32>,
+    size: Option<u64>,
+    fh: Option<u64>,
+    flags: Option<u32>,
+) -> Result<FileAttr, c_int> {
+    let mut data = DATA.lock().unwrap();
+    let resolved = data.resolve(ino);
+    let target = data.inos.get_mut(&resolved).ok_or(FILE_NOT_FOUND)?;
+    target.setattr(mode, uid, gid, size, fh, flags)?;
+    let mut attr = *target.attr();
+    attr.ino = ino;
+    Ok(attr)
+}
+
+pub fn mknod(parent: u64, name: &OsStr, mode: u32) -> Result<FileAttr, c_int> {
+    let mut data = DATA.lock().unwrap();
+    let parent = data.resolve(parent);
+    let parent = data.inos.get_mut(&parent).ok_or(FILE_NOT_FOUND)?.try_unwrap_dir_mut().ok_or(NOT_SUPPORTED)?;
+    if !parent.is_user_dir() && name.as_bytes().first() != Some(&b'_') {
+        return Err(PERMISSION_DENIED);
+    }
+
+    let new_ino = get_unique_ino();
+    parent.add_child(new_ino)?;
+
+    let (uid, gid) = request_owner();
+    let new_user_file = UserFile::new(name, new_ino, 0, mode, uid, gid, 0);
+    data.inos.insert(new_ino, Ino::File(Box::new(new_user_file)));
+    Ok(*data.inos.get(&new_ino).unwrap().attr())
+}
+
+pub fn mkdir(parent: u64, name: &OsStr, mode: u32) -> Result<FileAttr, c_int> {
+    let mut data = DATA.lock().unwrap();
+    let parent = data.resolve(parent);
+    let parent = data.inos.get_mut(&parent).ok_or(FILE_NOT_FOUND)?.try_unwrap_dir_mut().ok_or(NOT_SUPPORTED)?;
+    let is_user_dir = parent.is_user_dir() || name.as_bytes().first() == Some(&b'_');
+    if !is_user_dir {
+        return Err(PERMISSION_DENIED);
+    }
+
+    let new_ino = get_unique_ino();
+    parent.add_child(new_ino)?;
+
+    let (uid, gid) = request_owner();
+    let new_dir = NormalDir::new(name, is_user_dir, new_ino, mode, uid, gid, 0);
+    data.inos.insert(new_ino, Ino::Dir(Box::new(new_dir)));
+    Ok(*data.inos.get(&new_ino).unwrap().attr())
+}
+
+pub fn unlink(parent: u64, name: &OsStr) -> Result<(), c_int> {
+    let mut data = DATA.lock().unwrap();
+    let parent = data.resolve(parent);
+    let child = data.inos.get(&parent).ok_or(FILE_NOT_FOUND)?.try_unwrap_dir().ok_or(NOT_SUPPORTED)?.lookup_child(name, &data.inos)?;
+    data.inos.get_mut(&parent).unwrap().unwrap_dir_mut().remove_child(child)?;
+
+    if let Err(e) = data.inos.get_mut(&child).unwrap().delete() {
+        if data.inos.get_mut(&parent).unwrap().unwrap_dir_mut().add_child(child).is_err() {
+            warn!(ino = child; "unlink lost a file");
+        }
+        return Err(e);
+    }
+
+    data.inos.remove(&child);
+    Ok(())
+}
+
+pub fn rmdir(parent: u64, name: &OsStr) -> Result<(), c_int> {
+    let mut data = DATA.lock().unwrap();
+    let parent = data.resolve(parent);
+    let child_ino = data.inos.get(&parent).ok_or(FILE_NOT_FOUND)?.try_unwrap_dir().ok_or(NOT_SUPPORTED)?.lookup_child(name, &data.inos)?;
+    data.inos.get_mut(&parent).unwrap().unwrap_dir_mut().remove_child(child_ino)
+}
+
+pub fn rename(parent: u64, name: &OsStr, newparent: u64, newname: &OsStr) -> Result<(), c_int> {
+    let mut data = DATA.lock().unwrap();
+    let parent = data.resolve(parent);
+    let newparent = data.resolve(newparent);
+    if !data.inos.contains_key(&newparent) {
+        return Err(FILE_NOT_FOUND);
+    }
+    let child_ino = data.inos.get(&parent).ok_or(FILE_NOT_FOUND)?.try_unwrap_dir().ok_or(NOT_SUPPORTED)?.lookup_child(name, &data.inos)?;
+
+    let new_dir_is_user_dir = data.inos.get(&parent).unwrap().unwrap_dir().is_user_dir();
+
+    data.inos.get_mut(&child_ino).unwrap().rename(newname, new_dir_is_user_dir)?;
+    data.inos.get_mut(&parent).unwrap().unwrap_dir_mut().remove_child(child_ino)?;
+    if let Err(e) = data.inos.get_mut(&newparent).unwrap().try_unwrap_dir_mut().ok_or(NOT_SUPPORTED).and_then(|dir| dir.add_child(child_ino)) {
+        if data.inos.get_mut(&parent).unwrap().unwrap_dir_mut().add_child(child_ino).is_err() {
+            warn!(ino = child_ino; "rename dropped a file");
+        }
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Returns the file handle and the open flags for the kernel.
+pub fn open(ino: u64, flags: i32) -> Result<(u64, u32), c_int> {
+    let mut data = DATA.lock().unwrap();
+    let ino = data.resolve(ino);
+    let file = data.inos.get_mut(&ino).ok_or(FILE_NOT_FOUND)?.try_unwrap_file_mut().ok_or(NOT_SUPPORTED)?;
+    Ok((get_unique_ino(), file.open(flags)?))
+}
+
+pub fn read(ino: u64, offset: i64, size: u32, flags: i32) -> Result<Vec<u8>, c_int> {
+    let mut data = DATA.lock().unwrap();
+    let ino = data.resolve(ino);
+    let file = data.inos.get_mut(&ino).ok_or(FILE_NOT_FOUND)?.try_unwrap_file_mut().ok_or(NOT_SUPPORTED)?;
+    file.read(offset, size, flags).map(|x| x.to_vec())
+}
+
+pub fn write(ino: u64, offset: i64, write_data: &[u8], write_flags: u32, flags: i32) -> Result<u32, c_int> {
+    let mut data = DATA.lock().unwrap();
+    let ino = data.resolve(ino);
+    let file = data.inos.get_mut(&ino).ok_or(FILE_NOT_FOUND)?.try_unwrap_file_mut().ok_or(NOT_SUPPORTED)?;
+    file.write(offset, write_data, write_flags, flags)
+}
+
+pub fn release(ino: u64) -> Result<(), c_int> {
+    let mut data = DATA.lock().unwrap();
+    let ino = data.resolve(ino);
+    let file = data.inos.get_mut(&ino).ok_or(FILE_NOT_FOUND)?.try_unwrap_file_mut().ok_or(NOT_SUPPORTED)?;
+    file.release()
+}
+
+/// Entries of a directory starting at `offset`, each with the offset of the entry after it.
+pub fn readdir(ino: u64, offset: usize) -> Result<Vec<(u64, i64, FileType, OsString)>, c_int> {
+    let mut data = DATA.lock().unwrap();
+    let ino = data.resolve(ino);
+    let dir = data.inos.get(&ino).ok_or(FILE_NOT_FOUND)?.try_unwrap_dir().ok_or(NOT_SUPPORTED)?;
+
+    let mut entries = Vec::new();
+    let mut offset = offset;
+    while let Some(child) = dir.get_child(offset) {
+        let child_ino = data.inos.get(&child).unwrap();
+        entries.push((child, offset as i64 + 1, child_ino.attr().kind, child_ino.name().to_os_string()));
+        offset += 1;
+    }
+    Ok(entries)
+}
+
+/// Path of an ino as the current player sees it. Paths are remembered and checked before
+/// they are used, the whole tree is only searched for inos that moved or were never looked up.
+pub fn path_of(ino: u64) -> Option<String> {
+    let mut data = DATA.lock().unwrap();
+    let player = current_player();
+    let root = data.root(player);
+    let ino = data.resolve(ino);
+    if ino == root {
+        return Some("/".to_string());
+    }
+
+    let known = PATHS.lock().unwrap().get(&(player, ino)).cloned();
+    if let Some(path) = known.filter(|path| walk(&data, root, path) == Some(ino)) {
+        return Some(path);
+    }
+
+    let mut todo = vec![(root, String::new())];
+    while let Some((dir_ino, path)) = todo.pop() {
+        let Some(Ino::Dir(dir)) = data.inos.get(&dir_ino) else {
+            continue;
+        };
+        for child in (0..).map_while(|i| dir.get_child(i)) {
+            let Some(child_ino) = data.inos.get(&child) else {
+                continue;
+            };
+            let child_path = format!("{path}/{}", child_ino.name().to_string_lossy());
+            if child == ino {
+                PATHS.lock().unwrap().insert((player, ino), child_path.clone());
+                return Some(child_path);
+            }
+            todo.push((child, child_path));
+        }
+    }
+    None
+}
+
+/// The ino at `path` below `root`, without going through the files' own lookups.
+fn walk(data: &FsData, root: u64, path: &str) -> Option<u64> {
+    Path::new(path).components().try_fold(root, |ino, component| match component {
+        Component::Normal(name) => data.inos.get(&ino)?.try_unwrap_dir()?.lookup_child(name, &data.inos).ok(),
+        _ => Some(ino),
+    })
+}
+
+/// Remembers that the current player sees `ino` at `path`.
+pub fn remember_path(ino: u64, path: &str) {
+    PATHS.lock().unwrap().insert((current_player(), ino), path.to_string());
+}
+
+/// Forgets `path` and everything below it, after it was removed or moved.
+pub fn forget_path(path: &str) {
+    let player = current_player();
+    let below = format!("{path}/");
+    PATHS.lock().unwrap().retain(|(owner, _), known| *owner != player || (known != path && !known.starts_with(&below)));
+}
+
+/// Path of `name` inside the directory `parent`.
+pub fn child_path(parent: u64, name: &OsStr) -> Option<String> {
+    let parent = path_of(parent)?;
+    Some(format!("{}/{}", parent.trim_end_matches('/'), name.to_string_lossy()))
+}
+
+/// Looks a path up from the current player's root, the way the kernel would.
+pub fn resolve_path(path: &str) -> Result<u64, c_int> {
+    let mut ino = FUSE_ROOT_ID;
+    for component in Path::new(path).components() {
+        if let Component::Normal(name) = component {
+            ino = lookup(ino, name)?.ino;
+        }
+    }
+    Ok(ino)
+}
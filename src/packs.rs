@@ -0,0 +1,538 @@
+use std::{collections::{HashMap, HashSet}, fs, path::Path, sync::{Arc, Mutex}};
+
+use lazy_static::lazy_static;
+use serde::Deserialize;
+
+use crate::{background_tasks::{add_dir, add_file, replace_text, rm_file, DEFAULT_MODE}, file_helpers::{nothing, str_to_vec}, files::File, hints::Hint, module_state, main_fs::{get_unique_ino, players, with_player, SHARED_PLAYER}, modules::{progression, registry::{self, Module}}, progress::{self, Intro}, scripting::{HookedFile, Script, HOOK_OPS}, special_files::{delete_file::DeleteFile, open_count_file::OpenCountFile, order_file::{OrderFile, OrderState}, trigger_file::{Trigger, TriggerFile}}};
+
+pub const PACKS_DIR: &str = "./packs";
+
+lazy_static! {
+    static ref DIR: Mutex<String> = {
+        Mutex::new(PACKS_DIR.to_string())
+    };
+    /// What every installed pack was built from, to tell what a reload changed.
+    static ref INSTALLED: Mutex<HashMap<String, Arc<PackDef>>> = {
+        Mutex::new(HashMap::new())
+    };
+}
+
+/// A challenge pack, one per `.toml` or `.json` file in the packs directory.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PackDef {
+    pub id: String,
+    /// Directory the room lives in and where its `Victory` file appears. Defaults to `id`.
+    #[serde(default)]
+    pub dir: Option<String>,
+    #[serde(default)]
+    pub dirs: Vec<DirDef>,
+    #[serde(default)]
+    pub files: Vec<FileDef>,
+    /// Actions for `order` and `delete-count` triggers, keyed by group name.
+    #[serde(default)]
+    pub groups: HashMap<String, GroupDef>,
+    /// Rhai script holding the functions named by `hooks`, relative to the pack file.
+    #[serde(default)]
+    pub script: Option<String>,
+    #[serde(default)]
+    pub hooks: Vec<HookDef>,
+    #[serde(default)]
+    pub hints: Vec<Hint>,
+    /// One copy of the room for every player on the mount instead of one each.
+    #[serde(default)]
+    pub shared: bool,
+    /// Modules that have to be solved before this one opens, `"*"` for all of them.
+    #[serde(default)]
+    pub requires: Vec<String>,
+    #[serde(skip)]
+    pub compiled: Option<Script>,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HookDef {
+    /// Full path of the file, e.g. `Library/Shelf/Cats`.
+    pub path: String,
+    pub op: String,
+    pub call: String,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DirDef {
+    pub path: String,
+    #[serde(default = "default_mode")]
+    pub mode: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FileDef {
+    pub path: String,
+    pub name: String,
+    #[serde(default)]
+    pub content: String,
+    #[serde(default = "default_mode")]
+    pub mode: u32,
+    #[serde(default = "default_count")]
+    pub count: usize,
+    #[serde(default)]
+    pub trigger: Option<TriggerDef>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum TriggerDef {
+    /// Fires the first time the file is read.
+    ReadOnce {
+        #[serde(default)]
+        then: Vec<ActionDef>,
+    },
+    /// Shows `unlocked_content` and fires once `opens` handles are open at the same time.
+    OpenCount {
+        opens: u32,
+        #[serde(default)]
+        unlocked_content: String,
+        #[serde(default)]
+        then: Vec<ActionDef>,
+    },
+    /// The group fires once its files are read in `position` order, starting at 1.
+    Order {
+        group: String,
+        position: usize,
+        #[serde(default = "default_right")]
+        right: String,
+        #[serde(default = "default_wrong")]
+        wrong: String,
+    },
+    /// The group fires once every one of its files has been deleted.
+    DeleteCount {
+        group: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum ActionDef {
+    Victory,
+    Add {
+        file: FileDef,
+    },
+    Remove {
+        path: String,
+    },
+}
+
+#[derive(Debug, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GroupDef {
+    #[serde(default)]
+    pub then: Vec<ActionDef>,
+}
+
+fn default_mode() -> u32 {
+    DEFAULT_MODE
+}
+
+fn default_count() -> usize {
+    1
+}
+
+fn default_right() -> String {
+    "Yes\n".to_string()
+}
+
+fn default_wrong() -> String {
+    "No \n".to_string()
+}
+
+impl PackDef {
+    pub fn dir(&self) -> &str {
+        self.dir.as_deref().unwrap_or(&self.id)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.id.is_empty() {
+            return Err("pack id is empty".to_string());
+        }
+
+        let mut order_positions: HashMap<&str, Vec<usize>> = HashMap::new();
+        for file in &self.files {
+            validate_actions(file)?;
+            if file.name.is_empty() || file.name.contains('/') {
+                return Err(format!("invalid file name {:?}", file.name));
+            }
+            if Path::new(&file.path).is_absolute() {
+                return Err(format!("file path {:?} must be relative to the mount root", file.path));
+            }
+            match &file.trigger {
+                Some(TriggerDef::Order { group, position, .. }) => {
+                    if file.count != 1 {
+                        return Err(format!("order file {:?} can not have a count", file.name));
+                    }
+                    order_positions.entry(group).or_default().push(*position);
+                }
+                Some(TriggerDef::DeleteCount { group }) if !self.groups.contains_key(group) => {
+                    return Err(format!("unknown group {group:?}"));
+                }
+                _ => {}
+            }
+        }
+
+        for hook in &self.hooks {
+            if !HOOK_OPS.contains(&hook.op.as_str()) {
+                return Err(format!("unknown hook op {:?}", hook.op));
+            }
+            let Some(script) = &self.compiled else {
+                return Err("hooks need a script".to_string());
+            };
+            if !script.has_fn(&hook.call) {
+                return Err(format!("script has no function {:?}", hook.call));
+            }
+        }
+
+        for (group, mut positions) in order_positions {
+            if !self.groups.contains_key(group) {
+                return Err(format!("unknown group {group:?}"));
+            }
+            positions.sort();
+            if positions.iter().enumerate().any(|(i, pos)| *pos != i + 1) {
+                return Err(format!("order group {group:?} must use positions 1 to {}", positions.len()));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn validate_actions(file: &FileDef) -> Result<(), String> {
+    let actions = match &file.trigger {
+        Some(TriggerDef::ReadOnce { then }) | Some(TriggerDef::OpenCount { then, .. }) => then,
+        _ => return Ok(()),
+    };
+    for action in actions {
+        if let ActionDef::Add { file: added } = action {
+            if matches!(added.trigger, Some(TriggerDef::Order { .. }) | Some(TriggerDef::DeleteCount { .. })) {
+                return Err(format!("added file {:?} can not join a group", added.name));
+            }
+            validate_actions(added)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn parse(path: &Path) -> Result<PackDef, String> {
+    let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let mut pack: PackDef = match path.extension().and_then(|x| x.to_str()) {
+        Some("toml") => toml::from_str(&text).map_err(|e| e.to_string())?,
+        Some("json") => serde_json::from_str(&text).map_err(|e| e.to_string())?,
+        _ => return Err("unknown pack format".to_string()),
+    };
+    if let Some(script) = &pack.script {
+        let script_path = path.parent().unwrap_or(Path::new(".")).join(script);
+        let compiled = Script::compile(&pack.id, &script_path).map_err(|e| format!("{script}: {e}"))?;
+        pack.compiled = Some(compiled);
+    }
+    pack.validate()?;
+    Ok(pack)
+}
+
+/// The directory `reload` reads.
+pub fn set_dir(dir: &str) {
+    *DIR.lock().unwrap() = dir.to_string();
+}
+
+pub fn dir() -> String {
+    DIR.lock().unwrap().clone()
+}
+
+/// Loads every pack in `dir`, sorted by file name. A missing directory means no packs.
+pub fn load_dir(dir: &str) -> Result<Vec<PackDef>, String> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Ok(Vec::new());
+    };
+
+    let mut paths: Vec<_> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| matches!(path.extension().and_then(|x| x.to_str()), Some("toml" | "json")))
+        .collect();
+    paths.sort();
+
+    let mut ids = HashSet::new();
+    let mut packs = Vec::new();
+    for path in paths {
+        let pack = parse(&path).map_err(|e| format!("{}: {e}", path.display()))?;
+        if !ids.insert(pack.id.clone()) {
+            return Err(format!("{}: duplicate pack id {:?}", path.display(), pack.id));
+        }
+        packs.push(pack);
+    }
+    Ok(packs)
+}
+
+/// Registers every pack as a module, built alongside the built in ones.
+pub fn install(packs: Vec<PackDef>) {
+    for pack in packs {
+        let pack = Arc::new(pack);
+        let started = pack.clone();
+        let module = Module::new(&pack.id, pack.dir(), move || start_pack(started.clone()))
+            .hints(pack.hints.clone())
+            .shared(pack.shared)
+            .requires(pack.requires.clone());
+        INSTALLED.lock().unwrap().insert(pack.id.clone(), pack);
+        registry::register(module);
+    }
+}
+
+/// Loads the packs in `dir` again while mounted, or changes nothing if one of them is broken.
+/// Changed text is swapped into the files of players that have not solved the pack yet, other
+/// changes wait until a player starts or resets it. New packs show up for players past the
+/// intro, packs that are gone stay until the challenge restarts.
+pub fn reload(dir: &str) -> Result<String, String> {
+    let packs = load_dir(dir)?;
+    let installed = INSTALLED.lock().unwrap().clone();
+    let registered = registry::modules();
+
+    // Left out with `--modules` or `--exclude`.
+    let packs: Vec<PackDef> = packs.into_iter()
+        .filter(|pack| !installed.contains_key(&pack.id) || registered.iter().any(|module| module.id == pack.id))
+        .collect();
+
+    // Requirements from the command line stay.
+    let requires = |pack: &PackDef| {
+        let mut requires = pack.requires.clone();
+        if let (Some(module), Some(old)) = (registered.iter().find(|module| module.id == pack.id), installed.get(&pack.id)) {
+            requires.extend(module.requires.iter().filter(|name| !old.requires.contains(name)).cloned());
+        }
+        requires
+    };
+    let mut modules = registered.clone();
+    for pack in &packs {
+        match modules.iter_mut().find(|module| module.id == pack.id) {
+            Some(module) => module.requires = requires(pack),
+            None => modules.push(Module { id: pack.id.clone(), dir: pack.dir().to_string(), start: nothing.into(), hints: Vec::new(), shared: pack.shared, requires: requires(pack) }),
+        }
+    }
+    progression::check_modules(&modules)?;
+
+    let ids: HashSet<String> = packs.iter().map(|pack| pack.id.clone()).collect();
+    let (mut changed, mut reshaped, mut added) = (Vec::new(), Vec::new(), Vec::new());
+    let mut pending = Vec::new();
+    for pack in packs {
+        let requires = requires(&pack);
+        let Some(old) = installed.get(&pack.id) else {
+            added.push(pack.dir().to_string());
+            let id = pack.id.clone();
+            install(vec![pack]);
+            registry::set_requirements(&id, requires);
+            continue;
+        };
+
+        let texts = text_changes(old, &pack);
+        if !texts.is_empty() {
+            changed.push(pack.dir().to_string());
+        }
+        if !same_shape(old, &pack) {
+            reshaped.push(pack.dir().to_string());
+        }
+        for player in players().into_iter().chain([SHARED_PLAYER]) {
+            with_player(player, || {
+                if progress::is_solved(&pack.id) {
+                    return;
+                }
+                for (path, old, new) in &texts {
+                    pending.push(replace_text(path, old, new));
+                }
+            });
+        }
+        let id = pack.id.clone();
+        install(vec![pack]);
+        registry::set_requirements(&id, requires);
+    }
+    for pending in pending {
+        pending.wait()?;
+    }
+
+    for name in &added {
+        let Some(module) = registry::find(name) else {
+            continue;
+        };
+        for player in players() {
+            with_player(player, || {
+                if progress::intro() == Intro::Done {
+                    progression::show(&module);
+                }
+            });
+        }
+    }
+
+    let gone: Vec<String> = installed.values()
+        .filter(|old| !ids.contains(&old.id) && registered.iter().any(|module| module.id == old.id))
+        .map(|old| old.dir().to_string())
+        .collect();
+    let list = |names: &[String]| if names.is_empty() { "none".to_string() } else { names.join(", ") };
+    Ok(format!(
+        "Reloaded {}\nNew text: {}\nOther changes, for players that start or reset them: {}\nNew: {}\nGone, until the next restart: {}",
+        dir, list(&changed), list(&reshaped), list(&added), list(&gone),
+    ))
+}
+
+/// `(path, old, new)` for every file that only changed its text.
+fn text_changes(old: &PackDef, new: &PackDef) -> Vec<(String, String, String)> {
+    old.files.iter().zip(&new.files)
+        .filter(|(old, new)| old.content != new.content && FileDef { content: new.content.clone(), ..(*old).clone() } == **new)
+        .map(|(old, new)| (format!("{}/{}", old.path.trim_end_matches('/'), old.name), old.content.clone(), new.content.clone()))
+        .collect()
+}
+
+/// Whether the packs only differ in the text of their files.
+fn same_shape(old: &PackDef, new: &PackDef) -> bool {
+    old.dir() == new.dir()
+        && old.dirs == new.dirs
+        && old.groups == new.groups
+        && old.hooks == new.hooks
+        && old.script == new.script
+        && old.shared == new.shared
+        && old.requires == new.requires
+        && old.files.len() == new.files.len()
+        && old.files.iter().zip(&new.files).all(|(old, new)| FileDef { content: new.content.clone(), ..old.clone() } == *new)
+}
+
+fn start_pack(pack: Arc<PackDef>) {
+    for dir in &pack.dirs {
+        add_dir(&dir.path, dir.mode);
+    }
+
+    let mut orders = HashMap::new();
+    let mut deletes = HashMap::new();
+    for file in &pack.files {
+        match &file.trigger {
+            Some(TriggerDef::Order { group, .. }) => *orders.entry(group.clone()).or_insert(0) += 1,
+            Some(TriggerDef::DeleteCount { group }) => *deletes.entry(group.clone()).or_insert(0) += file.count,
+            _ => {}
+        }
+    }
+    let deletes_total = deletes.clone();
+
+    let groups = Groups {
+        orders: orders.into_iter().map(|(group, len)| {
+            let id = pack.id.clone();
+            let state = OrderState::saved(&pack.id, &order_key(&group), group_trigger(&pack, &group), move || progress::attempt(&id), len);
+            (group, state)
+        }).collect(),
+        deletes: deletes.into_iter().map(|(group, total)| {
+            let trigger = group_trigger(&pack, &group);
+            let (id, key) = (pack.id.clone(), delete_key(&group));
+            let trigger = Trigger::new(move || {
+                let left = module_state::get_u64(&id, &key, total as u64);
+                module_state::set(&id, &key, left.saturating_sub(1).into());
+                if left == 1 {
+                    trigger.fire();
+                }
+            });
+            (group, trigger)
+        }).collect(),
+    };
+
+    // Files already deleted from a group stay gone after a restart.
+    let mut deleted: HashMap<_, _> = deletes_total.into_iter()
+        .map(|(group, total)| {
+            let left = module_state::get_u64(&pack.id, &delete_key(&group), total as u64) as usize;
+            (group, total.saturating_sub(left))
+        })
+        .collect();
+    for file in &pack.files {
+        for _ in 0..file.count {
+            if let Some(TriggerDef::DeleteCount { group }) = &file.trigger {
+                if let Some(skip @ 1..) = deleted.get_mut(group) {
+                    *skip -= 1;
+                    continue;
+                }
+            }
+            add_file(&file.path, build_file(&pack, &groups, file));
+        }
+    }
+}
+
+fn order_key(group: &str) -> String {
+    format!("order.{group}")
+}
+
+fn delete_key(group: &str) -> String {
+    format!("deletes_left.{group}")
+}
+
+struct Groups {
+    orders: HashMap<String, Arc<Mutex<OrderState>>>,
+    deletes: HashMap<String, Trigger>,
+}
+
+fn group_trigger(pack: &Arc<PackDef>, group: &str) -> Trigger {
+    let actions = pack.groups.get(group).map(|group| group.then.clone()).unwrap_or_default();
+    actions_trigger(pack, actions)
+}
+
+fn actions_trigger(pack: &Arc<PackDef>, actions: Vec<ActionDef>) -> Trigger {
+    let pack = pack.clone();
+    Trigger::new(move || {
+        for action in &actions {
+            run_action(&pack, action);
+        }
+    })
+}
+
+fn run_action(pack: &Arc<PackDef>, action: &ActionDef) {
+    match action {
+        ActionDef::Victory => registry::victory(&pack.id),
+        ActionDef::Remove { path } => {
+            rm_file(path);
+        }
+        ActionDef::Add { file } => {
+            let groups = Groups { orders: HashMap::new(), deletes: HashMap::new() };
+            for _ in 0..file.count {
+                add_file(&file.path, build_file(pack, &groups, file));
+            }
+        }
+    }
+}
+
+fn build_file(pack: &Arc<PackDef>, groups: &Groups, file: &FileDef) -> Box<dyn File> {
+    let inner = build_behaviour(pack, groups, file);
+    let Some(script) = &pack.compiled else {
+        return inner;
+    };
+
+    let path = format!("{}/{}", file.path.trim_matches('/'), file.name);
+    let hooks: HashMap<_, _> = pack.hooks.iter()
+        .filter(|hook| hook.path.trim_matches('/') == path)
+        .map(|hook| (hook.op.clone(), hook.call.clone()))
+        .collect();
+    if hooks.is_empty() {
+        return inner;
+    }
+    Box::new(HookedFile::new(inner, &path, hooks, script.clone()))
+}
+
+fn build_behaviour(pack: &Arc<PackDef>, groups: &Groups, file: &FileDef) -> Box<dyn File> {
+    let content = str_to_vec(&file.content);
+    match &file.trigger {
+        None => Box::new(TriggerFile::new(nothing, &file.name, content, get_unique_ino(), file.mode, 0)),
+        Some(TriggerDef::ReadOnce { then }) => {
+            Box::new(TriggerFile::new(actions_trigger(pack, then.clone()), &file.name, content, get_unique_ino(), file.mode, 0))
+        }
+        Some(TriggerDef::OpenCount { opens, unlocked_content, then }) => {
+            let trigger = actions_trigger(pack, then.clone());
+            Box::new(OpenCountFile::new(trigger, &file.name, content, str_to_vec(unlocked_content), *opens, get_unique_ino(), file.mode))
+        }
+        Some(TriggerDef::Order { group, position, right, wrong }) => {
+            let state = groups.orders.get(group)
+                .cloned()
+                .unwrap_or_else(|| OrderState::new(nothing, nothing, 0));
+            Box::new(OrderFile::new(state, position - 1, &file.name, str_to_vec(right), str_to_vec(wrong), get_unique_ino(), file.mode))
+        }
+        Some(TriggerDef::DeleteCount { group }) => {
+            let trigger = groups.deletes.get(group).cloned().unwrap_or_else(|| nothing.into());
+            Box::new(DeleteFile::new(trigger, &file.name, content, get_unique_ino(), file.mode))
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use std::{collections::BTreeMap, sync::Mutex, time::{SystemTime, UNIX_EPOCH}};
+
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+
+use crate::{difficulty::Difficulty, hints, main_fs::current_player, save};
+
+lazy_static! {
+    static ref PROGRESS: Mutex<BTreeMap<u32, Progress>> = {
+        Mutex::new(BTreeMap::new())
+    };
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Intro {
+    #[default]
+    Welcome,
+    Hub,
+    Done,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModuleProgress {
+    pub started: Option<u64>,
+    pub solved: Option<u64>,
+    #[serde(default)]
+    pub attempts: u32,
+    #[serde(default)]
+    pub hints: u32,
+    /// What the module was built with, it stays the same until the module is reset.
+    #[serde(default)]
+    pub difficulty: Option<Difficulty>,
+}
+
+/// Progress of one player.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Progress {
+    pub intro: Intro,
+    pub modules: BTreeMap<String, ModuleProgress>,
+    /// Language the player picked, see `messages`.
+    #[serde(default)]
+    pub locale: Option<String>,
+}
+
+impl Progress {
+    pub fn solved(&self) -> u32 {
+        self.modules.values().filter(|x| x.solved.is_some()).count() as u32
+    }
+
+    pub fn attempts(&self) -> u32 {
+        self.modules.values().map(|x| x.attempts).sum()
+    }
+
+    pub fn hints(&self) -> u32 {
+        self.modules.values().map(|x| x.hints).sum()
+    }
+}
+
+pub fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|x| x.as_secs()).unwrap_or(0)
+}
+
+fn update<T>(f: impl FnOnce(&mut Progress) -> T) -> T {
+    let out = f(PROGRESS.lock().unwrap().entry(current_player()).or_default());
+    save::save_later();
+    out
+}
+
+/// Progress of the current player.
+pub fn snapshot() -> Progress {
+    PROGRESS.lock().unwrap().get(&current_player()).cloned().unwrap_or_default()
+}
+
+/// Progress of every player, keyed by uid.
+pub fn all() -> BTreeMap<u32, Progress> {
+    PROGRESS.lock().unwrap().clone()
+}
+
+pub fn restore(progress: BTreeMap<u32, Progress>) {
+    *PROGRESS.lock().unwrap() = progress;
+}
+
+pub fn intro() -> Intro {
+    snapshot().intro
+}
+
+pub fn set_intro(intro: Intro) {
+    update(|progress| progress.intro = intro);
+}
+
+pub fn locale() -> Option<String> {
+    PROGRESS.lock().unwrap().get(&current_player()).and_then(|x| x.locale.clone())
+}
+
+pub fn set_locale(locale: &str) {
+    update(|progress| progress.locale = Some(locale.to_string()));
+}
+
+pub fn started(module: &str, difficulty: Difficulty) {
+    update(|progress| {
+        let module = progress.modules.entry(module.to_string()).or_default();
+        module.started.get_or_insert_with(now);
+        module.difficulty.get_or_insert(difficulty);
+    });
+}
+
+pub fn is_solved(module: &str) -> bool {
+    snapshot().modules.get(module).is_some_and(|x| x.solved.is_some())
+}
+
+/// Returns false if the module was already solved.
+pub fn solve(module: &str) -> bool {
+    update(|progress| {
+        let module = progress.modules.entry(module.to_string()).or_default();
+        if module.solved.is_some() {
+            return false;
+        }
+        module.solved = Some(now());
+        true
+    })
+}
+
+/// Forgets everything about the module for the current player.
+pub fn reset(module: &str) {
+    update(|progress| progress.modules.remove(module));
+}
+
+/// Records a wrong attempt at a module, such as reading the sorter files out of order.
+pub fn attempt(module: &str) {
+    update(|progress| progress.modules.entry(module.to_string()).or_default().attempts += 1);
+    hints::check(module);
+}
+
+/// Counts one more unlocked hint and returns how many the module has now.
+pub fn hint_used(module: &str) -> u32 {
+    update(|progress| {
+        let module = progress.modules.entry(module.to_string()).or_default();
+        module.hints += 1;
+        module.hints
+    })
+}
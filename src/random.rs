@@ -0,0 +1,59 @@
+use std::sync::Mutex;
+
+use lazy_static::lazy_static;
+use log::{info, warn};
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use sha2::{Digest, Sha256};
+
+use crate::main_fs::current_player;
+
+lazy_static! {
+    static ref SEED: Mutex<Seed> = {
+        Mutex::new(Seed::default())
+    };
+}
+
+#[derive(Debug, Default)]
+struct Seed {
+    value: Option<u64>,
+    /// Given on the command line, so it wins over the one in the save file.
+    fixed: bool,
+}
+
+/// Uses `seed` for this run, whatever the save file says.
+pub fn set_seed(seed: u64) {
+    *SEED.lock().unwrap() = Seed { value: Some(seed), fixed: true };
+}
+
+/// The seed every puzzle parameter comes from, drawn on first use if nobody set one.
+pub fn seed() -> u64 {
+    *SEED.lock().unwrap().value.get_or_insert_with(|| {
+        let seed = rand::random();
+        info!(seed = seed; "drew a new seed");
+        seed
+    })
+}
+
+/// Takes the seed of a save file, unless one was given on the command line.
+pub fn restore(seed: Option<u64>) {
+    let mut current = SEED.lock().unwrap();
+    match (seed, current.value) {
+        (Some(saved), Some(given)) if current.fixed && saved != given => {
+            warn!("using seed {given} instead of {saved} from the save file, puzzles will change for players that already started");
+        }
+        (Some(saved), _) if !current.fixed => current.value = Some(saved),
+        _ => {}
+    }
+}
+
+/// A generator for the current player and `module`. It starts over on every call, so a
+/// module draws the same parameters whenever it is built or checked for that player.
+pub fn rng(module: &str) -> ChaCha8Rng {
+    let digest = Sha256::new()
+        .chain_update(seed().to_le_bytes())
+        .chain_update(module.as_bytes())
+        .chain_update(current_player().to_le_bytes())
+        .finalize();
+    ChaCha8Rng::from_seed(digest.into())
+}
@@ -0,0 +1,107 @@
+use std::{ffi::{c_int, OsStr}, path::Path, thread, time::Duration};
+
+use log::warn;
+
+use crate::{audit::Record, errors::NOT_SUPPORTED, main_fs::{begin_request, RequestInfo}, ops, random};
+
+/// How long to wait for a path that existed in the recording. Triggers add files
+/// in the background, so they can show up a little after the operation that caused them.
+const PATH_WAIT: Duration = Duration::from_secs(1);
+const PATH_POLL: Duration = Duration::from_millis(20);
+
+/// Feeds an audit log into the filesystem of this process, which should be fresh.
+/// Prints every operation whose result differs from the recording and returns how many did.
+pub fn replay(records: &[Record], uid: Option<u32>) -> usize {
+    let mut replayed = 0;
+    let mut diverged = 0;
+    for (i, record) in records.iter().enumerate() {
+        if record.op == "session" {
+            if record.seed.is_some_and(|seed| seed != random::seed()) {
+                warn!("#{} was recorded with seed {}, the puzzles from here on differ", i + 1, record.seed.unwrap_or_default());
+            }
+            continue;
+        }
+        if uid.is_some_and(|uid| uid != record.uid) || record.path == "?" {
+            continue;
+        }
+
+        begin_request(RequestInfo { uid: record.uid, gid: record.gid, pid: record.pid });
+        let errno = run(record);
+        replayed += 1;
+        if errno != record.errno {
+            diverged += 1;
+            println!("#{} {} {} by {} ({}): recorded errno {}, replayed errno {}", i + 1, record.op, record.path, record.process, record.uid, record.errno, errno);
+        }
+    }
+    println!("replayed {replayed} operations, {diverged} diverged");
+    diverged
+}
+
+fn errno<T>(result: Result<T, c_int>) -> c_int {
+    result.err().unwrap_or(0)
+}
+
+fn split(path: &str) -> (&str, &OsStr) {
+    let path = Path::new(path);
+    let parent = path.parent().and_then(|x| x.to_str()).unwrap_or("/");
+    (parent, path.file_name().unwrap_or_default())
+}
+
+/// Looks the path up, waiting for it if the recording says it was there.
+fn resolve(path: &str, wait: bool) -> Result<u64, c_int> {
+    let mut waited = Duration::ZERO;
+    loop {
+        match ops::resolve_path(path) {
+            Err(_) if wait && waited < PATH_WAIT => {
+                thread::sleep(PATH_POLL);
+                waited += PATH_POLL;
+            }
+            result => return result,
+        }
+    }
+}
+
+fn run(record: &Record) -> c_int {
+    let found = record.errno == 0;
+    let (parent, name) = split(&record.path);
+    let result = match record.op.as_str() {
+        "lookup" => resolve(parent, true).and_then(|parent| {
+            // Give a file the recording found time to show up.
+            let _ = resolve(&record.path, found);
+            ops::lookup(parent, name)
+        }).map(|_| ()),
+        "getattr" => resolve(&record.path, found).and_then(ops::getattr).map(|_| ()),
+        "setattr" => resolve(&record.path, true)
+            .and_then(|ino| ops::setattr(ino, record.mode, None, None, record.set_size, None, None))
+            .map(|_| ()),
+        "mknod" => resolve(parent, true).and_then(|parent| ops::mknod(parent, name, record.mode.unwrap_or(0o644))).map(|_| ()),
+        "mkdir" => resolve(parent, true).and_then(|parent| ops::mkdir(parent, name, record.mode.unwrap_or(0o755))).map(|_| ()),
+        "unlink" => resolve(parent, true).and_then(|parent| ops::unlink(parent, name)),
+        "rmdir" => resolve(parent, true).and_then(|parent| ops::rmdir(parent, name)),
+        "rename" => {
+            let new_path = record.new_path.clone().unwrap_or_default();
+            let (new_parent, new_name) = split(&new_path);
+            resolve(parent, true).and_then(|parent| {
+                let new_parent = resolve(new_parent, true)?;
+                ops::rename(parent, name, new_parent, new_name)
+            })
+        }
+        "open" => resolve(&record.path, true).and_then(|ino| ops::open(ino, record.flags.unwrap_or(0))).map(|_| ()),
+        "read" => resolve(&record.path, true)
+            .and_then(|ino| ops::read(ino, record.offset.unwrap_or(0), record.size.unwrap_or(4096), record.flags.unwrap_or(0)))
+            .map(|_| ()),
+        "write" => resolve(&record.path, true).and_then(|ino| {
+            let data = record.data.as_deref().unwrap_or_default();
+            ops::write(ino, record.offset.unwrap_or(0), data, record.write_flags.unwrap_or(0), record.flags.unwrap_or(0))
+        }).map(|_| ()),
+        "release" => resolve(&record.path, true).and_then(ops::release),
+        "readdir" => resolve(&record.path, true)
+            .and_then(|ino| ops::readdir(ino, record.offset.unwrap_or(0).max(0) as usize))
+            .map(|_| ()),
+        other => {
+            warn!("can not replay unknown operation {other}");
+            Err(NOT_SUPPORTED)
+        }
+    };
+    errno(result)
+}
@@ -0,0 +1,147 @@
+use std::{collections::BTreeMap, fs, path::PathBuf, sync::{mpsc::{self, Sender}, Mutex}, thread, time::Duration};
+
+use lazy_static::lazy_static;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{main_fs::UID, module_state::{self, PlayerState}, progress::{self, Progress}, random};
+
+pub const DEFAULT_STATE_DIR: &str = "./state";
+const SAVE_FILE: &str = "save.json";
+/// Saves that can not be read are moved here instead of being overwritten.
+const BROKEN_SAVE_FILE: &str = "save.json.broken";
+/// 1 had a single player, 2 keys everything by uid.
+pub const SAVE_VERSION: u32 = 2;
+/// How long `save_later` collects changes before writing them.
+const SAVE_DELAY: Duration = Duration::from_millis(500);
+
+lazy_static! {
+    static ref STATE_DIR: Mutex<PathBuf> = {
+        Mutex::new(PathBuf::from(DEFAULT_STATE_DIR))
+    };
+    /// Held while the save file is written, so the writer and `save` never mix their files.
+    static ref WRITING: Mutex<()> = {
+        Mutex::new(())
+    };
+    static ref WRITER: Mutex<Sender<()>> = {
+        let (sender, receiver) = mpsc::channel();
+        thread::Builder::new()
+            .name("save".to_string())
+            .spawn(move || {
+                while receiver.recv().is_ok() {
+                    thread::sleep(SAVE_DELAY);
+                    while receiver.try_recv().is_ok() {}
+                    save();
+                }
+            })
+            .expect("Failed to start the save writer");
+        Mutex::new(sender)
+    };
+}
+
+/// Everything is keyed by player uid.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SaveFile {
+    #[serde(default)]
+    pub version: u32,
+    pub progress: BTreeMap<u32, Progress>,
+    pub state: BTreeMap<u32, PlayerState>,
+    /// Puzzle parameters come from it, see `random`.
+    #[serde(default)]
+    pub seed: Option<u64>,
+}
+
+/// Version 1, from before every player had their own tree.
+#[derive(Debug, Deserialize)]
+struct SinglePlayerSave {
+    progress: Progress,
+    #[serde(default)]
+    state: PlayerState,
+}
+
+pub fn set_state_dir(dir: &str) {
+    *STATE_DIR.lock().unwrap() = PathBuf::from(dir);
+}
+
+pub fn state_dir() -> PathBuf {
+    STATE_DIR.lock().unwrap().clone()
+}
+
+/// Saves a little later on a thread of its own, together with whatever else changes until
+/// then. For changes made in filesystem operations, which should not wait for the disk.
+pub fn save_later() {
+    let _ = WRITER.lock().unwrap().send(());
+}
+
+/// Writes progress and module state to the save file. Errors are only reported,
+/// a full disk should not take the challenge down with it.
+pub fn save() {
+    let _writing = WRITING.lock().unwrap();
+    let save = SaveFile {
+        version: SAVE_VERSION,
+        progress: progress::all(),
+        state: module_state::snapshot(),
+        seed: Some(random::seed()),
+    };
+    if let Err(e) = write(&save) {
+        warn!("failed to save progress: {e}");
+    }
+}
+
+fn write(save: &SaveFile) -> std::io::Result<()> {
+    let dir = state_dir();
+    fs::create_dir_all(&dir)?;
+    let tmp = dir.join(format!("{SAVE_FILE}.tmp"));
+    fs::write(&tmp, serde_json::to_vec_pretty(save)?)?;
+    fs::rename(tmp, dir.join(SAVE_FILE))
+}
+
+/// Reads a save file of any version. Saves without a version field are version 1 if their
+/// progress is a single player's, which then belongs to the user running the challenge.
+pub fn parse(text: &[u8]) -> Result<SaveFile, String> {
+    let value: Value = serde_json::from_slice(text).map_err(|e| e.to_string())?;
+    let version = match value.get("version").and_then(Value::as_u64) {
+        Some(version) => version,
+        None if value.get("progress").is_some_and(|x| x.get("intro").is_some()) => 1,
+        None => SAVE_VERSION as u64,
+    };
+    match version {
+        1 => {
+            let old: SinglePlayerSave = serde_json::from_value(value).map_err(|e| e.to_string())?;
+            info!("moving the progress of a single player save to uid {}", *UID);
+            Ok(SaveFile {
+                version: SAVE_VERSION,
+                progress: BTreeMap::from([(*UID, old.progress)]),
+                state: BTreeMap::from([(*UID, old.state)]),
+                seed: None,
+            })
+        }
+        2 => serde_json::from_value(value).map_err(|e| e.to_string()),
+        _ => Err(format!("version {version} is newer than this build")),
+    }
+}
+
+/// Loads the save file if there is one. Returns whether anything was restored.
+pub fn load() -> bool {
+    let path = state_dir().join(SAVE_FILE);
+    let Ok(text) = fs::read(&path) else {
+        return false;
+    };
+    let save = match parse(&text) {
+        Ok(save) => save,
+        Err(e) => {
+            let broken = state_dir().join(BROKEN_SAVE_FILE);
+            warn!("can not read the save file {}, moving it to {}: {e}", path.display(), broken.display());
+            if let Err(e) = fs::rename(&path, &broken) {
+                warn!("failed to move {}, it will be overwritten: {e}", path.display());
+            }
+            return false;
+        }
+    };
+
+    progress::restore(save.progress);
+    module_state::restore(save.state);
+    random::restore(save.seed);
+    true
+}
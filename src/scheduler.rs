@@ -0,0 +1,164 @@
+use std::{collections::{BTreeMap, HashMap}, sync::{atomic::{AtomicU64, Ordering}, Arc, Condvar, Mutex}, thread, time::{Duration, Instant}};
+
+use lazy_static::lazy_static;
+use log::{trace, warn};
+
+use crate::{background_tasks::batch, main_fs::{current_player, with_player}};
+
+// Jobs belong to a player and a module, like the files they usually add or remove, and run
+// as that player on the "scheduler" thread. Each job is one batch of tree changes.
+//
+// Time is counted from the start of the process. Tests can stop the clock of a single player
+// and move it by hand, so jobs of other players, and other tests, keep running on real time.
+
+lazy_static! {
+    static ref JOBS: Mutex<Jobs> = {
+        thread::Builder::new()
+            .name("scheduler".to_string())
+            .spawn(worker)
+            .expect("Failed to start the scheduler");
+        Mutex::new(Jobs::default())
+    };
+    static ref WAKE: Condvar = Condvar::new();
+    static ref START: Instant = Instant::now();
+}
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct JobId(pub u64);
+
+struct Job {
+    player: u32,
+    module: String,
+    due: Duration,
+    every: Option<Duration>,
+    f: Arc<dyn Fn() + Send + Sync>,
+}
+
+#[derive(Default)]
+struct Jobs {
+    jobs: BTreeMap<JobId, Job>,
+    /// Players whose clock is stopped, with the time it shows.
+    paused: HashMap<u32, Duration>,
+}
+
+impl Jobs {
+    fn now(&self, player: u32) -> Duration {
+        self.paused.get(&player).copied().unwrap_or_else(|| START.elapsed())
+    }
+
+    /// The job that is due first, of `only` or of every player whose clock is running.
+    fn next(&self, only: Option<u32>) -> Option<(JobId, Duration)> {
+        self.jobs.iter()
+            .filter(|(_, job)| match only {
+                Some(player) => job.player == player,
+                None => !self.paused.contains_key(&job.player),
+            })
+            .map(|(id, job)| (*id, job.due))
+            .min_by_key(|(id, due)| (*due, *id))
+    }
+}
+
+/// Runs `f` once, `delay` from now.
+pub fn after(module: &str, delay: Duration, f: impl Fn() + Send + Sync + 'static) -> JobId {
+    schedule(module, delay, None, Arc::new(f))
+}
+
+/// Runs `f` every `period`, starting one period from now, until it is cancelled.
+pub fn every(module: &str, period: Duration, f: impl Fn() + Send + Sync + 'static) -> JobId {
+    let period = period.max(Duration::from_millis(1));
+    schedule(module, period, Some(period), Arc::new(f))
+}
+
+/// Returns false if the job already ran or was cancelled.
+pub fn cancel(id: JobId) -> bool {
+    JOBS.lock().unwrap().jobs.remove(&id).is_some()
+}
+
+/// Cancels the current player's jobs of a module, for resets.
+pub fn cancel_module(module: &str) {
+    let player = current_player();
+    JOBS.lock().unwrap().jobs.retain(|_, job| job.player != player || job.module != module);
+}
+
+/// Stops the current player's clock. Their jobs only run from `advance` after this.
+#[cfg(test)]
+pub fn pause() {
+    let player = current_player();
+    let mut jobs = JOBS.lock().unwrap();
+    let now = jobs.now(player);
+    jobs.paused.insert(player, now);
+}
+
+/// Moves the current player's stopped clock forward and runs their jobs that came due on
+/// the calling thread, in order.
+#[cfg(test)]
+pub fn advance(by: Duration) {
+    let player = current_player();
+    let until = JOBS.lock().unwrap().paused.get(&player).copied().expect("advance without pause") + by;
+    loop {
+        let mut jobs = JOBS.lock().unwrap();
+        let Some((id, due)) = jobs.next(Some(player)).filter(|(_, due)| *due <= until) else {
+            jobs.paused.insert(player, until);
+            return;
+        };
+        // Jobs scheduled by this one count from when it was due.
+        jobs.paused.insert(player, due);
+        drop(jobs);
+        run(id);
+    }
+}
+
+fn schedule(module: &str, delay: Duration, every: Option<Duration>, f: Arc<dyn Fn() + Send + Sync>) -> JobId {
+    let player = current_player();
+    let id = JobId(NEXT_ID.fetch_add(1, Ordering::Relaxed));
+    let mut jobs = JOBS.lock().unwrap();
+    let due = jobs.now(player) + delay;
+    trace!(job = id.0, player = player, module = module; "scheduled in {delay:?}");
+    jobs.jobs.insert(id, Job { player, module: module.to_string(), due, every, f });
+    WAKE.notify_one();
+    id
+}
+
+/// Takes the job off the list, or moves it to its next period, and runs it.
+fn run(id: JobId) {
+    let (player, f) = {
+        let mut jobs = JOBS.lock().unwrap();
+        let Some(job) = jobs.jobs.get_mut(&id) else {
+            return;
+        };
+        let out = (job.player, job.f.clone());
+        match job.every {
+            Some(period) => job.due += period,
+            None => {
+                jobs.jobs.remove(&id);
+            }
+        }
+        out
+    };
+
+    if let Err(e) = with_player(player, || batch(|| f())).wait() {
+        warn!(job = id.0, player = player; "scheduled job failed: {e}");
+    }
+}
+
+fn worker() {
+    loop {
+        let jobs = JOBS.lock().unwrap();
+        match jobs.next(None) {
+            Some((id, due)) => {
+                let now = START.elapsed();
+                if due <= now {
+                    drop(jobs);
+                    run(id);
+                } else {
+                    drop(WAKE.wait_timeout(jobs, due - now).unwrap());
+                }
+            }
+            None => {
+                drop(WAKE.wait(jobs).unwrap());
+            }
+        }
+    }
+}
@@ -0,0 +1,199 @@
+use std::{collections::BTreeMap, fmt::Write as _, fs::{self, OpenOptions}, io::Write, net::{SocketAddr, ToSocketAddrs}, sync::Mutex, thread};
+
+use lazy_static::lazy_static;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::{flags, main_fs::current_player, messages::Message, modules::registry, progress, save, special_files::progress_file::user_name};
+
+pub const SUBMIT_FILE: &str = "Submit";
+pub const SCOREBOARD_FILE: &str = "Scoreboard";
+
+pub const USAGE: Message = Message::new("scoreboard", "usage").at(SUBMIT_FILE);
+pub const REJECTED: Message = Message::new("scoreboard", "rejected");
+const ACCEPTED: Message = Message::new("scoreboard", "accepted");
+const FIRST_BLOOD: Message = Message::new("scoreboard", "first_blood");
+const INVALID: Message = Message::new("scoreboard", "invalid");
+const NOT_YOURS: Message = Message::new("scoreboard", "not_yours");
+const ALREADY: Message = Message::new("scoreboard", "already");
+const NOT_RECORDED: Message = Message::new("scoreboard", "not_recorded");
+const RANK: Message = Message::new("scoreboard", "rank");
+const PLAYER: Message = Message::new("scoreboard", "player");
+const SOLVED: Message = Message::new("scoreboard", "solved");
+const FIRST_BLOODS: Message = Message::new("scoreboard", "first_bloods");
+pub const MESSAGES: &[Message] = &[USAGE, REJECTED, ACCEPTED, FIRST_BLOOD, INVALID, NOT_YOURS, ALREADY, NOT_RECORDED, RANK, PLAYER, SOLVED, FIRST_BLOODS];
+
+const STORE_FILE: &str = "scoreboard.jsonl";
+
+lazy_static! {
+    static ref SUBMISSIONS: Mutex<Option<Vec<Submission>>> = {
+        Mutex::new(None)
+    };
+}
+
+/// One accepted flag. The store is a file with one of these per line, only ever appended to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Submission {
+    pub uid: u32,
+    pub module: String,
+    pub time: u64,
+    pub first_blood: bool,
+}
+
+/// Standing of one player, in scoreboard order.
+#[derive(Debug, Clone, Serialize)]
+pub struct Standing {
+    pub rank: usize,
+    pub uid: u32,
+    pub name: String,
+    pub solved: usize,
+    pub first_bloods: usize,
+    pub last: u64,
+}
+
+fn with_submissions<T>(f: impl FnOnce(&mut Vec<Submission>) -> T) -> T {
+    let mut submissions = SUBMISSIONS.lock().unwrap();
+    let submissions = submissions.get_or_insert_with(load);
+    f(submissions)
+}
+
+fn load() -> Vec<Submission> {
+    let path = save::state_dir().join(STORE_FILE);
+    let Ok(text) = fs::read_to_string(&path) else {
+        return Vec::new();
+    };
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| match serde_json::from_str(line) {
+            Ok(submission) => Some(submission),
+            Err(e) => {
+                warn!("skipping broken line in {}: {e}", path.display());
+                None
+            }
+        })
+        .collect()
+}
+
+fn append(submission: &Submission) -> std::io::Result<()> {
+    let dir = save::state_dir();
+    fs::create_dir_all(&dir)?;
+    let mut file = OpenOptions::new().create(true).append(true).open(dir.join(STORE_FILE))?;
+    writeln!(file, "{}", serde_json::to_string(submission)?)
+}
+
+pub fn submissions() -> Vec<Submission> {
+    with_submissions(|submissions| submissions.clone())
+}
+
+/// Checks a flag written by the current player and records it.
+pub fn submit(flag: &str) -> Result<String, String> {
+    let (module, uid) = flags::verify(flag).map_err(|e| INVALID.format(&[("error", &e)]))?;
+    let player = current_player();
+    if uid != player {
+        return Err(NOT_YOURS.text());
+    }
+    let name = registry::get(&module).map(|module| module.dir).unwrap_or_else(|| module.clone());
+
+    with_submissions(|submissions| {
+        if submissions.iter().any(|x| x.uid == uid && x.module == module) {
+            return Err(ALREADY.format(&[("module", &name)]));
+        }
+
+        let submission = Submission {
+            uid,
+            first_blood: !submissions.iter().any(|x| x.module == module),
+            module: module.clone(),
+            time: progress::now(),
+        };
+        append(&submission).map_err(|e| NOT_RECORDED.format(&[("error", &e)]))?;
+        submissions.push(submission.clone());
+
+        if submission.first_blood {
+            Ok(FIRST_BLOOD.format(&[("module", &name)]))
+        } else {
+            Ok(ACCEPTED.format(&[("module", &name)]))
+        }
+    })
+}
+
+/// Players ordered by solved modules, then first bloods, then who got there first.
+pub fn standings() -> Vec<Standing> {
+    let mut players: BTreeMap<u32, Standing> = BTreeMap::new();
+    for submission in submissions() {
+        let standing = players.entry(submission.uid).or_insert_with(|| Standing {
+            rank: 0,
+            uid: submission.uid,
+            name: user_name(submission.uid),
+            solved: 0,
+            first_bloods: 0,
+            last: 0,
+        });
+        standing.solved += 1;
+        standing.first_bloods += submission.first_blood as usize;
+        standing.last = standing.last.max(submission.time);
+    }
+
+    let mut standings: Vec<Standing> = players.into_values().collect();
+    standings.sort_by_key(|x| (std::cmp::Reverse(x.solved), std::cmp::Reverse(x.first_bloods), x.last, x.uid));
+    for (i, standing) in standings.iter_mut().enumerate() {
+        standing.rank = i + 1;
+    }
+    standings
+}
+
+pub fn render() -> String {
+    let mut out = String::new();
+    writeln!(out, "{:<6} {:<20} {:<8} {}", RANK.text(), PLAYER.text(), SOLVED.text(), FIRST_BLOODS.text()).unwrap();
+    for standing in standings() {
+        writeln!(out, "{:<6} {:<20} {:<8} {}", standing.rank, standing.name, standing.solved, standing.first_bloods).unwrap();
+    }
+
+    let firsts: Vec<Submission> = submissions().into_iter().filter(|x| x.first_blood).collect();
+    if !firsts.is_empty() {
+        writeln!(out, "\n{}", FIRST_BLOODS.text()).unwrap();
+        for submission in firsts {
+            let name = registry::get(&submission.module).map(|module| module.dir).unwrap_or(submission.module);
+            writeln!(out, "{:<20} {}", name, user_name(submission.uid)).unwrap();
+        }
+    }
+    out
+}
+
+/// `port` alone means localhost.
+fn resolve(addr: &str) -> Result<Vec<SocketAddr>, String> {
+    let addr = if addr.parse::<u16>().is_ok() { format!("127.0.0.1:{addr}") } else { addr.to_string() };
+    let addrs: Vec<SocketAddr> = addr.to_socket_addrs().map_err(|e| format!("{addr}: {e}"))?.collect();
+    if addrs.is_empty() {
+        return Err(format!("{addr} does not resolve to an address"));
+    }
+    Ok(addrs)
+}
+
+/// Serves the scoreboard on `addr` for projectors: plain text on `/` and JSON on `/json`.
+/// There is no authentication, so addresses other than loopback are refused unless `public`.
+pub fn serve(addr: &str, public: bool) -> Result<(), String> {
+    let addrs = resolve(addr)?;
+    if let Some(open) = addrs.iter().find(|x| !x.ip().is_loopback()) {
+        if !public {
+            return Err(format!("{open} is reachable from other machines and shows every player's standings, pass --scoreboard-public to serve it anyway"));
+        }
+        warn!("serving the scoreboard without authentication on {open}");
+    }
+    let server = tiny_http::Server::http(&addrs[..]).map_err(|e| e.to_string())?;
+    let addr = addrs[0];
+    info!("serving the scoreboard on http://{addr}/");
+    thread::spawn(move || {
+        for request in server.incoming_requests() {
+            let (body, content_type) = match request.url() {
+                "/json" => (serde_json::to_string_pretty(&standings()).unwrap(), "application/json"),
+                _ => (render(), "text/plain; charset=utf-8"),
+            };
+            let header = tiny_http::Header::from_bytes("Content-Type", content_type).unwrap();
+            let response = tiny_http::Response::from_string(body).with_header(header);
+            if let Err(e) = request.respond(response) {
+                warn!("scoreboard request failed: {e}");
+            }
+        }
+    });
+    Ok(())
+}
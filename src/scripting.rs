@@ -0,0 +1,278 @@
+use std::{cell::RefCell, collections::HashMap, ffi::{c_int, OsStr}, path::Path, sync::Arc, time::Duration};
+
+use fuser::{consts::FOPEN_DIRECT_IO, FileAttr};
+use lazy_static::lazy_static;
+use log::warn;
+use rhai::{serde::{from_dynamic, to_dynamic}, Dynamic, Engine, EvalAltResult, Map, Scope, AST};
+use serde_json::Value;
+
+use crate::{background_tasks::{add_file, rm_file}, errors::{IO_ERROR, PERMISSION_DENIED}, file_helpers::{read, str_to_vec, text_file}, files::File, main_fs::{current_request, get_unique_ino}, module_state, modules::registry, progress, scheduler::{self, JobId}, special_files::trigger_file::TriggerFile};
+
+pub const HOOK_OPS: [&str; 5] = ["open", "read", "write", "release", "delete"];
+
+// Hooks run inside filesystem operations with the tree locked, so a runaway script would
+// hang the mount for every player. These stop it well within a second.
+const MAX_OPERATIONS: u64 = 1_000_000;
+const MAX_CALL_LEVELS: usize = 32;
+const MAX_STRING_SIZE: usize = 1 << 20;
+const MAX_COLLECTION_SIZE: usize = 10_000;
+
+lazy_static! {
+    static ref ENGINE: Engine = new_engine();
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<Script>> = const { RefCell::new(None) };
+}
+
+/// A compiled pack script together with the module it belongs to.
+#[derive(Debug, Clone)]
+pub struct Script {
+    pub module: String,
+    pub ast: Arc<AST>,
+}
+
+impl Script {
+    pub fn compile(module: &str, path: &Path) -> Result<Script, String> {
+        let ast = ENGINE.compile_file(path.to_path_buf()).map_err(|e| e.to_string())?;
+        Ok(Script { module: module.to_string(), ast: Arc::new(ast) })
+    }
+
+    pub fn has_fn(&self, name: &str) -> bool {
+        self.ast.iter_functions().any(|f| f.name == name)
+    }
+
+    /// Runs the function `name`. Script errors are logged and count as no answer, a script
+    /// that hits one of the limits is an `IO_ERROR`.
+    pub fn call(&self, name: &str, ctx: Map) -> Result<Dynamic, c_int> {
+        let previous = CURRENT.with(|current| current.replace(Some(self.clone())));
+        let out = ENGINE.call_fn::<Dynamic>(&mut Scope::new(), &self.ast, name, (ctx,));
+        CURRENT.with(|current| current.replace(previous));
+
+        out.or_else(|e| {
+            warn!(module = self.module.as_str(); "script error in {}::{name}: {e}", self.module);
+            match e.unwrap_inner() {
+                EvalAltResult::ErrorTooManyOperations(..)
+                | EvalAltResult::ErrorTooManyVariables(..)
+                | EvalAltResult::ErrorTooManyModules(..)
+                | EvalAltResult::ErrorStackOverflow(..)
+                | EvalAltResult::ErrorDataTooLarge(..) => Err(IO_ERROR),
+                _ => Ok(Dynamic::UNIT),
+            }
+        })
+    }
+}
+
+fn current() -> Script {
+    CURRENT.with(|current| current.borrow().clone()).expect("Script function called outside of a hook")
+}
+
+fn new_engine() -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS)
+        .set_max_call_levels(MAX_CALL_LEVELS)
+        .set_max_string_size(MAX_STRING_SIZE)
+        .set_max_array_size(MAX_COLLECTION_SIZE)
+        .set_max_map_size(MAX_COLLECTION_SIZE);
+
+    engine.register_fn("add_file", |path: &str, name: &str, content: &str| {
+        add_file(path, text_file(name, content));
+    });
+    engine.register_fn("add_file", |path: &str, name: &str, content: &str, mode: i64| {
+        add_file(path, Box::new(TriggerFile::new(|| {}, name, str_to_vec(content), get_unique_ino(), mode as u32, 0)));
+    });
+    engine.register_fn("remove_file", |path: &str| {
+        rm_file(path);
+    });
+    engine.register_fn("victory", || {
+        registry::victory(&current().module);
+    });
+
+    engine.register_fn("state_get", |key: &str| {
+        state_get(&current().module, key)
+    });
+    engine.register_fn("state_get", |module: &str, key: &str| {
+        state_get(module, key)
+    });
+    engine.register_fn("state_set", |key: &str, value: Dynamic| {
+        match from_dynamic::<Value>(&value) {
+            Ok(value) => module_state::set(&current().module, key, value),
+            Err(e) => warn!("script error: can not store {key}: {e}"),
+        }
+    });
+
+    engine.register_fn("attempt", || {
+        progress::attempt(&current().module);
+    });
+    engine.register_fn("difficulty", || {
+        registry::difficulty(&current().module).to_string()
+    });
+    engine.register_fn("request", || {
+        request_map()
+    });
+    engine.register_fn("after", |ms: i64, name: &str| {
+        let (module, call) = scheduled_call(name);
+        scheduler::after(&module, Duration::from_millis(ms.max(0) as u64), call).0 as i64
+    });
+    engine.register_fn("every", |ms: i64, name: &str| {
+        let (module, call) = scheduled_call(name);
+        scheduler::every(&module, Duration::from_millis(ms.max(0) as u64), call).0 as i64
+    });
+    engine.register_fn("cancel", |job: i64| {
+        scheduler::cancel(JobId(job as u64))
+    });
+
+    engine
+}
+
+/// Calls the current script's function `name` from the scheduler.
+fn scheduled_call(name: &str) -> (String, impl Fn() + Send + Sync + 'static) {
+    let script = current();
+    let name = name.to_string();
+    (script.module.clone(), move || {
+        let _ = script.call(&name, Map::new());
+    })
+}
+
+fn state_get(module: &str, key: &str) -> Dynamic {
+    module_state::get(module, key)
+        .and_then(|value| to_dynamic(value).ok())
+        .unwrap_or(Dynamic::UNIT)
+}
+
+fn request_map() -> Map {
+    let mut map = Map::new();
+    if let Some(req) = current_request() {
+        map.insert("uid".into(), (req.uid as i64).into());
+        map.insert("gid".into(), (req.gid as i64).into());
+        map.insert("pid".into(), (req.pid as i64).into());
+    }
+    map
+}
+
+/// Wraps a pack file and runs script hooks before its operations.
+/// A hook returning `false` denies the operation, `true` accepts it without
+/// asking the wrapped file and a string returned from `read` replaces the content.
+#[derive(Debug)]
+pub struct HookedFile {
+    pub inner: Box<dyn File>,
+    pub path: String,
+    pub hooks: HashMap<String, String>,
+    pub script: Script,
+    pub data: Vec<u8>,
+    /// The wrapped file's attributes, with the size of the last text the read hook returned.
+    pub attr: FileAttr,
+}
+
+impl HookedFile {
+    pub fn new(inner: Box<dyn File>, path: &str, hooks: HashMap<String, String>, script: Script) -> Self {
+        let attr = *inner.attr();
+        Self { inner, path: path.to_string(), hooks, script, data: Vec::new(), attr }
+    }
+
+    fn hook(&self, op: &str, extra: &[(&str, Dynamic)]) -> Result<Dynamic, c_int> {
+        let Some(name) = self.hooks.get(op) else {
+            return Ok(Dynamic::UNIT);
+        };
+
+        let mut ctx = request_map();
+        ctx.insert("op".into(), op.into());
+        ctx.insert("path".into(), self.path.clone().into());
+        for (key, value) in extra {
+            ctx.insert((*key).into(), value.clone());
+        }
+        self.script.call(name, ctx)
+    }
+}
+
+impl File for HookedFile {
+    fn name(&self) -> &OsStr {
+        self.inner.name()
+    }
+
+    fn attr(&self) -> &FileAttr {
+        &self.attr
+    }
+
+    fn setattr(
+        &mut self,
+        mode: Option<u32>,
+        uid: Option<u32>,
+        gid: Option<u32>,
+        size: Option<u64>,
+        fh: Option<u64>,
+        flags: Option<u32>,
+    ) -> Result<(), c_int> {
+        // Shells truncate before writing, let that through to the write hook.
+        if self.hooks.contains_key("write") && size == Some(0) && mode.is_none() && uid.is_none() && gid.is_none() {
+            return Ok(());
+        }
+        let result = self.inner.setattr(mode, uid, gid, size, fh, flags);
+        self.attr = *self.inner.attr();
+        result
+    }
+
+    fn rename(&mut self, new_name: &OsStr, in_user_dir: bool) -> Result<(), c_int> {
+        let result = self.inner.rename(new_name, in_user_dir);
+        self.attr = *self.inner.attr();
+        result
+    }
+
+    fn read(&mut self, offset: i64, size: u32, flags: i32) -> Result<&[u8], c_int> {
+        let out = self.hook("read", &[("offset", offset.into()), ("size", (size as i64).into())])?;
+        if out.is_string() {
+            self.data = str_to_vec(&out.into_string().unwrap());
+            self.attr.size = self.data.len() as u64;
+            return read(&self.data, offset, size);
+        }
+        match out.as_bool() {
+            Ok(false) => Err(PERMISSION_DENIED),
+            _ => {
+                self.attr = *self.inner.attr();
+                self.inner.read(offset, size, flags)
+            }
+        }
+    }
+
+    fn write(&mut self, offset: i64, data: &[u8], write_flags: u32, flags: i32) -> Result<u32, c_int> {
+        let text = String::from_utf8_lossy(data).to_string();
+        match self.hook("write", &[("offset", offset.into()), ("data", text.into())])?.as_bool() {
+            Ok(true) => Ok(data.len() as u32),
+            Ok(false) => Err(PERMISSION_DENIED),
+            Err(_) => {
+                let result = self.inner.write(offset, data, write_flags, flags);
+                self.attr = *self.inner.attr();
+                result
+            }
+        }
+    }
+
+    fn delete(&mut self) -> Result<(), c_int> {
+        match self.hook("delete", &[])?.as_bool() {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(PERMISSION_DENIED),
+            Err(_) => self.inner.delete(),
+        }
+    }
+
+    fn open(&mut self, flags: i32) -> Result<u32, c_int> {
+        match self.hook("open", &[("flags", (flags as i64).into())])?.as_bool() {
+            Ok(false) => Err(PERMISSION_DENIED),
+            // The read hook can change the content at any time, so the kernel must not cache it.
+            _ if self.hooks.contains_key("read") => self.inner.open(flags).map(|open_flags| open_flags | FOPEN_DIRECT_IO),
+            _ => self.inner.open(flags),
+        }
+    }
+
+    fn release(&mut self) -> Result<(), c_int> {
+        let _ = self.hook("release", &[]);
+        self.inner.release()
+    }
+
+    fn replace_text(&mut self, old: &[u8], new: &[u8]) -> bool {
+        let replaced = self.inner.replace_text(old, new);
+        self.attr = *self.inner.attr();
+        replaced
+    }
+}
+
@@ -0,0 +1,138 @@
+use std::{collections::{BTreeMap, BTreeSet}, mem, path::PathBuf, process, ptr, sync::Mutex, thread, time::Instant};
+
+use lazy_static::lazy_static;
+use log::{info, warn};
+
+use crate::{audit, background_tasks, cli, control_socket, modules::registry, progress, save};
+
+const SIGNALS: [libc::c_int; 3] = [libc::SIGINT, libc::SIGTERM, libc::SIGHUP];
+
+lazy_static! {
+    static ref STATS: Mutex<Stats> = {
+        Mutex::new(Stats::default())
+    };
+    static ref START: Instant = Instant::now();
+}
+
+/// What the summary at the end is made of.
+#[derive(Debug, Default)]
+struct Stats {
+    ops: BTreeMap<String, u64>,
+    players: BTreeSet<u32>,
+}
+
+/// Counts one filesystem operation of `uid`.
+pub fn count(op: &str, uid: u32) {
+    let mut stats = STATS.lock().unwrap();
+    match stats.ops.get_mut(op) {
+        Some(count) => *count += 1,
+        None => {
+            stats.ops.insert(op.to_string(), 1);
+        }
+    }
+    stats.players.insert(uid);
+}
+
+/// Handles Ctrl-C, `kill` and a closed terminal on a thread of their own. The first one
+/// unmounts, which ends `mount2` like any unmount does, a second one exits right away.
+/// Has to be called before other threads are started, they inherit the blocked signals.
+pub fn catch_signals(mount_point: PathBuf) {
+    lazy_static::initialize(&START);
+    let set = unsafe {
+        let mut set: libc::sigset_t = mem::zeroed();
+        libc::sigemptyset(&mut set);
+        for signal in SIGNALS {
+            libc::sigaddset(&mut set, signal);
+        }
+        libc::pthread_sigmask(libc::SIG_BLOCK, &set, ptr::null_mut());
+        set
+    };
+
+    let handler = move || {
+        let mut received = 0;
+        loop {
+            let mut signal = 0;
+            if unsafe { libc::sigwait(&set, &mut signal) } != 0 {
+                continue;
+            }
+            received += 1;
+            if received > 1 {
+                warn!("got {} again, exiting without unmounting", name(signal));
+                // Queued tree changes may be what is stuck, so they are not waited for.
+                write_out();
+                report();
+                process::exit(128 + signal);
+            }
+
+            info!("got {}, unmounting {}", name(signal), mount_point.display());
+            if let Err(e) = cli::unmount(&mount_point, false) {
+                warn!("failed to unmount, something still uses the challenge:\n{e}");
+                match cli::unmount(&mount_point, true) {
+                    Ok(()) => info!("detached {}, stopping once nothing uses it anymore, send another signal to stop now", mount_point.display()),
+                    Err(e) => warn!("failed to detach {}, send another signal to stop anyway:\n{e}", mount_point.display()),
+                }
+            }
+        }
+    };
+    thread::Builder::new()
+        .name("signals".to_string())
+        .spawn(handler)
+        .expect("Failed to start the signal handler");
+}
+
+fn name(signal: libc::c_int) -> &'static str {
+    match signal {
+        libc::SIGINT => "SIGINT",
+        libc::SIGTERM => "SIGTERM",
+        libc::SIGHUP => "SIGHUP",
+        _ => "a signal",
+    }
+}
+
+/// Writes out everything that is still in flight: queued tree changes, progress and the
+/// audit log. Also takes the control socket down.
+pub fn finish() {
+    background_tasks::flush();
+    write_out();
+}
+
+/// `finish` without waiting for the queued tree changes.
+fn write_out() {
+    save::save();
+    audit::close();
+    control_socket::close();
+}
+
+/// How the session went, one line per topic.
+pub fn summary() -> Vec<String> {
+    let stats = STATS.lock().unwrap();
+    let progress = progress::all();
+    let players: Vec<String> = stats.players.iter().map(|x| x.to_string()).collect();
+    let solved: Vec<String> = registry::modules().iter()
+        .map(|module| {
+            let solved = stats.players.iter()
+                .filter(|uid| progress.get(uid).and_then(|x| x.modules.get(&module.id)).is_some_and(|x| x.solved.is_some()))
+                .count();
+            format!("{} by {solved}", module.dir)
+        })
+        .collect();
+    let mut ops: Vec<(&String, &u64)> = stats.ops.iter().collect();
+    ops.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+    let total: u64 = stats.ops.values().sum();
+    let ops: Vec<String> = ops.iter().map(|(op, count)| format!("{op} {count}")).collect();
+
+    let elapsed = START.elapsed().as_secs();
+    vec![
+        format!("ran for {}h {:02}m {:02}s", elapsed / 3600, elapsed / 60 % 60, elapsed % 60),
+        format!("players seen: {} ({})", players.len(), players.join(", ")),
+        format!("solved: {}", solved.join(", ")),
+        format!("{total} operations: {}", ops.join(", ")),
+    ]
+}
+
+/// Logs the summary.
+pub fn report() {
+    for line in summary() {
+        info!("{line}");
+    }
+}
@@ -0,0 +1,212 @@
+use std::{ffi::{c_int, OsStr}, path::Path, sync::atomic::{AtomicU32, Ordering}, time::Duration};
+
+use fuser::{FileAttr, FUSE_ROOT_ID};
+
+use crate::{difficulty::Difficulty, harness, main_fs::{begin_request, with_player, RequestInfo}, modules::registry, ops, progress, scheduler};
+
+/// Uids handed out by `Sim::player`, far away from real users.
+static NEXT_UID: AtomicU32 = AtomicU32::new(100_000);
+
+const READ_SIZE: u32 = 4096;
+
+/// A player using the filesystem without a kernel in between. Every call goes through the
+/// same `ops` as `MainFs`, as if the request came from `request`, so modules and packs can
+/// be tested without `/dev/fuse`. Paths are relative to the player's root, like in `add_file`.
+#[derive(Debug, Clone, Copy)]
+pub struct Sim {
+    pub request: RequestInfo,
+}
+
+/// An open file, from `Sim::open`.
+#[derive(Debug)]
+pub struct Handle {
+    pub ino: u64,
+    pub flags: i32,
+}
+
+impl Sim {
+    pub fn new(uid: u32, gid: u32, pid: u32) -> Sim {
+        harness::dir();
+        Sim { request: RequestInfo { uid, gid, pid } }
+    }
+
+    /// A player nobody else has used yet, so every test gets a fresh tree.
+    pub fn player() -> Sim {
+        let uid = NEXT_UID.fetch_add(1, Ordering::Relaxed);
+        Sim::new(uid, uid, std::process::id())
+    }
+
+    fn enter(&self) {
+        begin_request(self.request);
+    }
+
+    /// Builds one module for this player, whether or not the intro is done.
+    pub fn start(&self, id: &str) {
+        let module = registry::get(id).unwrap_or_else(|| panic!("unknown module {id}"));
+        self.enter();
+        registry::start(&module);
+    }
+
+    /// Builds one module at `difficulty`, whatever the instructor picked.
+    pub fn start_at(&self, id: &str, difficulty: Difficulty) {
+        with_player(self.request.uid, || progress::started(id, difficulty));
+        self.start(id);
+    }
+
+    /// A fresh player after reading both welcome files, with every module built.
+    pub fn after_intro() -> Sim {
+        // Tests register packs as they go, only the modules from before the intro are built.
+        let modules = registry::modules();
+        let sim = Sim::player();
+        assert!(sim.wait_for("Welcome"), "Welcome never showed up");
+        sim.read_file("Welcome").expect("Failed to read Welcome");
+        assert!(sim.wait_for("Welcome?"), "Welcome? never showed up");
+        sim.read_file("Welcome?").expect("Failed to read Welcome?");
+        for module in modules {
+            assert!(sim.wait_for(&module.dir), "{} never showed up", module.dir);
+        }
+        sim
+    }
+
+    /// Stops this player's clock, see `scheduler::pause`.
+    pub fn pause_clock(&self) {
+        with_player(self.request.uid, scheduler::pause);
+    }
+
+    /// Runs this player's jobs that come due in the next `by`.
+    pub fn advance(&self, by: Duration) {
+        with_player(self.request.uid, || scheduler::advance(by));
+    }
+
+    fn resolve(&self, path: &str) -> Result<u64, c_int> {
+        self.enter();
+        ops::resolve_path(path)
+    }
+
+    fn parent(&self, path: &str) -> Result<(u64, String), c_int> {
+        let path = Path::new(path);
+        let name = path.file_name().ok_or(libc::EINVAL)?.to_string_lossy().into_owned();
+        let parent = match path.parent() {
+            Some(parent) => self.resolve(&parent.to_string_lossy())?,
+            None => FUSE_ROOT_ID,
+        };
+        Ok((parent, name))
+    }
+
+    pub fn lookup(&self, path: &str) -> Result<FileAttr, c_int> {
+        let (parent, name) = self.parent(path)?;
+        ops::lookup(parent, OsStr::new(&name))
+    }
+
+    pub fn exists(&self, path: &str) -> bool {
+        self.lookup(path).is_ok()
+    }
+
+    /// Waits up to `harness::TIMEOUT` for a path to appear. Triggers add files from background threads.
+    pub fn wait_for(&self, path: &str) -> bool {
+        harness::wait_until(|| self.exists(path))
+    }
+
+    pub fn open(&self, path: &str, flags: i32) -> Result<Handle, c_int> {
+        let ino = self.resolve(path)?;
+        ops::open(ino, flags)?;
+        Ok(Handle { ino, flags })
+    }
+
+    pub fn read(&self, handle: &Handle, offset: i64, size: u32) -> Result<Vec<u8>, c_int> {
+        self.enter();
+        ops::read(handle.ino, offset, size, handle.flags)
+    }
+
+    pub fn write(&self, handle: &Handle, offset: i64, data: &[u8]) -> Result<u32, c_int> {
+        self.enter();
+        ops::write(handle.ino, offset, data, 0, handle.flags)
+    }
+
+    pub fn release(&self, handle: Handle) -> Result<(), c_int> {
+        self.enter();
+        ops::release(handle.ino)
+    }
+
+    /// Opens with `flags` and reads until a short read, like `cat` on a cached file.
+    pub fn read_with(&self, path: &str, flags: i32) -> Result<String, c_int> {
+        let handle = self.open(path, flags)?;
+        let mut out = Vec::new();
+        let result = loop {
+            match self.read(&handle, out.len() as i64, READ_SIZE) {
+                Ok(data) => {
+                    out.extend_from_slice(&data);
+                    if data.len() < READ_SIZE as usize {
+                        break Ok(());
+                    }
+                }
+                Err(e) => break Err(e),
+            }
+        };
+        self.release(handle)?;
+        result.map(|_| String::from_utf8_lossy(&out).into_owned())
+    }
+
+    pub fn read_file(&self, path: &str) -> Result<String, c_int> {
+        self.read_with(path, libc::O_RDONLY)
+    }
+
+    /// Creates or truncates the file and writes `data`, like a shell redirect.
+    pub fn write_file(&self, path: &str, data: &str) -> Result<(), c_int> {
+        if !self.exists(path) {
+            self.create(path, 0o644)?;
+        }
+        self.truncate(path, 0)?;
+        let handle = self.open(path, libc::O_WRONLY | libc::O_TRUNC)?;
+        let result = self.write(&handle, 0, data.as_bytes());
+        self.release(handle)?;
+        result.map(|_| ())
+    }
+
+    pub fn truncate(&self, path: &str, size: u64) -> Result<(), c_int> {
+        let ino = self.resolve(path)?;
+        ops::setattr(ino, None, None, None, Some(size), None, None).map(|_| ())
+    }
+
+    pub fn chmod(&self, path: &str, mode: u32) -> Result<(), c_int> {
+        let ino = self.resolve(path)?;
+        ops::setattr(ino, Some(mode), None, None, None, None, None).map(|_| ())
+    }
+
+    pub fn create(&self, path: &str, mode: u32) -> Result<FileAttr, c_int> {
+        let (parent, name) = self.parent(path)?;
+        ops::mknod(parent, OsStr::new(&name), libc::S_IFREG | mode)
+    }
+
+    pub fn mkdir(&self, path: &str, mode: u32) -> Result<FileAttr, c_int> {
+        let (parent, name) = self.parent(path)?;
+        ops::mkdir(parent, OsStr::new(&name), mode)
+    }
+
+    pub fn unlink(&self, path: &str) -> Result<(), c_int> {
+        let (parent, name) = self.parent(path)?;
+        ops::unlink(parent, OsStr::new(&name))
+    }
+
+    pub fn rmdir(&self, path: &str) -> Result<(), c_int> {
+        let (parent, name) = self.parent(path)?;
+        ops::rmdir(parent, OsStr::new(&name))
+    }
+
+    pub fn rename(&self, from: &str, to: &str) -> Result<(), c_int> {
+        let (parent, name) = self.parent(from)?;
+        let (new_parent, new_name) = self.parent(to)?;
+        ops::rename(parent, OsStr::new(&name), new_parent, OsStr::new(&new_name))
+    }
+
+    /// Names in a directory, sorted.
+    pub fn readdir(&self, path: &str) -> Result<Vec<String>, c_int> {
+        let ino = self.resolve(path)?;
+        let mut names: Vec<String> = ops::readdir(ino, 0)?
+            .into_iter()
+            .map(|(_, _, _, name)| name.to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        Ok(names)
+    }
+}
@@ -0,0 +1,84 @@
+use std::{path::Path, process::Command};
+
+use crate::{harness::{self, TIMEOUT}, modules::registry};
+
+/// Reference solutions in the solutions crate and the module each one beats.
+const SOLUTIONS: [(&str, &str); 3] = [
+    ("the_door", "many_open"),
+    ("classroom", "classroom"),
+    ("bathroom", "bathroom"),
+];
+
+/// Builds the solutions crate in `dir`, runs every solution from inside its module on a
+/// fresh mount and checks that the module's `Victory` file shows up. Returns the exit code.
+pub fn check(dir: &Path) -> i32 {
+    // The solutions run from inside the mount, so their path can not be relative.
+    let dir = &match dir.canonicalize() {
+        Ok(dir) => dir,
+        Err(e) => {
+            eprintln!("Failed to find the solutions in {}: {e}", dir.display());
+            return 2;
+        }
+    };
+    // Built into a target dir of our own, `CARGO_TARGET_DIR` would put the binaries elsewhere.
+    let target = dir.join("target");
+    let built = Command::new("cargo").arg("build").arg("--quiet")
+        .arg("--manifest-path").arg(dir.join("Cargo.toml"))
+        .arg("--target-dir").arg(&target)
+        .status();
+    match built {
+        Ok(status) if status.success() => {}
+        Ok(status) => {
+            eprintln!("Failed to build the solutions: cargo {status}");
+            return 2;
+        }
+        Err(e) => {
+            eprintln!("Failed to run cargo: {e}");
+            return 2;
+        }
+    }
+
+    let Some(mount) = harness::mount() else {
+        eprintln!("could not mount the challenge");
+        return 2;
+    };
+
+    let mut passed = 0;
+    let mut failed = 0;
+    for (solution, id) in SOLUTIONS {
+        let Some(module) = registry::get(id) else {
+            println!("SKIP {solution}: module {id} is not registered");
+            continue;
+        };
+        let victory = format!("{}/Victory", module.dir);
+        if mount.exists(&victory) {
+            println!("FAIL {} ({solution}): solved before the solution ran", module.dir);
+            failed += 1;
+            continue;
+        }
+
+        let result = Command::new(target.join("debug").join(solution)).current_dir(mount.path(&module.dir)).output();
+        let error = match result {
+            Ok(_) if mount.wait_for(&victory) => None,
+            Ok(output) => {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                Some(format!("no Victory after {}s, solution {}\n{}", TIMEOUT.as_secs(), output.status, stderr.trim_end()).trim_end().to_string())
+            }
+            Err(e) => Some(format!("could not run it: {e}")),
+        };
+        match error {
+            None => {
+                println!("PASS {} ({solution})", module.dir);
+                passed += 1;
+            }
+            Some(e) => {
+                println!("FAIL {} ({solution}): {e}", module.dir);
+                failed += 1;
+            }
+        }
+    }
+
+    mount.unmount();
+    println!("{passed} passed, {failed} failed");
+    if failed > 0 { 1 } else { 0 }
+}
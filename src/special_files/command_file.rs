@@ -0,0 +1,132 @@
+use std::{ffi::{c_int, OsStr, OsString}, str::FromStr, time::SystemTime};
+
+use fuser::{consts::FOPEN_DIRECT_IO, FileAttr};
+
+use crate::{errors::PERMISSION_DENIED, file_helpers::{read, str_to_vec}, files::File, main_fs::{get_unique_ino, GID, UID}, messages::Message};
+
+/// Writable file that hands every line written to it to `run`, like `.control` and `Submit`.
+/// Reading it shows the usage and the result of the last line.
+#[derive(Debug)]
+pub struct CommandFile {
+    pub attr: FileAttr,
+    pub name: OsString,
+    pub usage: String,
+    pub run: fn(&str) -> Result<String, String>,
+    /// Put in front of errors from `run`, like `Error`.
+    pub error: Message,
+    pub data: Vec<u8>,
+    pub pending: Vec<u8>,
+}
+
+impl CommandFile {
+    pub fn new(name: &str, usage: Message, run: fn(&str) -> Result<String, String>, error: Message) -> Self {
+        let usage = usage.text();
+        let data = str_to_vec(&usage);
+        Self {
+            attr: FileAttr { 
+                ino: get_unique_ino(), 
+                size: data.len() as u64, 
+                blocks: 0, 
+                atime: SystemTime::now(), 
+                mtime: SystemTime::now(), 
+                ctime: SystemTime::now(), 
+                crtime: SystemTime::now(), 
+                kind: fuser::FileType::RegularFile, 
+                perm: 0o666, 
+                nlink: 0, 
+                uid: *UID, 
+                gid: *GID, 
+                rdev: 0, 
+                blksize: 0, 
+                flags: 0,
+            },
+            name: OsString::from_str(name).unwrap(),
+            usage,
+            run,
+            error,
+            data,
+            pending: Vec::new(),
+        }
+    }
+
+    fn run_lines(&mut self) {
+        while let Some(end) = self.pending.iter().position(|x| *x == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line).trim().to_string();
+            if line.is_empty() {
+                continue;
+            }
+            let result = (self.run)(&line).unwrap_or_else(|e| format!("{}: {e}", self.error.text()));
+            self.data = str_to_vec(&format!("{}\n> {line}\n{result}\n", self.usage));
+            self.attr.size = self.data.len() as u64;
+        }
+    }
+}
+
+impl File for CommandFile {
+    fn name(&self) -> &OsStr {
+        &self.name
+    }
+
+    fn attr(&self) -> &FileAttr {
+        &self.attr
+    }
+
+    fn setattr(
+        &mut self, 
+        _mode: Option<u32>,
+        _uid: Option<u32>,
+        _gid: Option<u32>,
+        size: Option<u64>,
+        _fh: Option<u64>,
+        _flags: Option<u32>,
+    ) -> Result<(), c_int> {
+        // Shell redirection truncates before writing.
+        if size == Some(0) {
+            return Ok(());
+        }
+        Err(PERMISSION_DENIED)
+    }
+    
+    fn rename(&mut self, _new_name: &OsStr, _in_user_dir: bool) -> Result<(), c_int> {
+        Err(PERMISSION_DENIED)
+    }
+    
+    fn read(&mut self, offset: i64, size: u32, _flags: i32) -> Result<&[u8], c_int> {
+        read(&self.data, offset, size)
+    }
+    
+    fn write(&mut self, _offset: i64, data: &[u8], _write_flags: u32, _flags: i32) -> Result<u32, c_int> {
+        self.pending.extend_from_slice(data);
+        self.run_lines();
+        Ok(data.len() as u32)
+    }
+    
+    fn delete(&mut self) -> Result<(), c_int> {
+        Err(PERMISSION_DENIED)
+    }
+
+    fn open(&mut self, _flags: i32) -> Result<u32, c_int> {
+        Ok(FOPEN_DIRECT_IO)
+    }
+
+    /// Swaps the usage when the player changes their language, the last result stays.
+    fn replace_text(&mut self, old: &[u8], new: &[u8]) -> bool {
+        if self.usage.as_bytes() != old {
+            return false;
+        }
+        self.data = [new, &self.data[old.len()..]].concat();
+        self.usage = String::from_utf8_lossy(new).into_owned();
+        self.attr.size = self.data.len() as u64;
+        true
+    }
+
+    fn release(&mut self) -> Result<(), c_int> {
+        // A line without a trailing newline still counts once the writer is done.
+        if !self.pending.is_empty() {
+            self.pending.push(b'\n');
+            self.run_lines();
+        }
+        Ok(())
+    }
+}
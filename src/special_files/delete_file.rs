@@ -0,0 +1,85 @@
+use std::{ffi::{c_int, OsStr, OsString}, str::FromStr, time::SystemTime};
+
+use fuser::FileAttr;
+
+use crate::{errors::PERMISSION_DENIED, file_helpers::{read, replace_text}, files::File, main_fs::{GID, UID}};
+
+use super::trigger_file::Trigger;
+
+#[derive(Debug)]
+pub struct DeleteFile {
+    pub attr: FileAttr,
+    pub name: OsString,
+    pub data: Vec<u8>,
+    pub trigger: Trigger,
+}
+
+impl DeleteFile {
+    pub fn new(trigger: impl Into<Trigger>, name: &str, data: Vec<u8>, ino: u64, mode: u32) -> Self {
+        Self {
+            attr: FileAttr { 
+                ino, 
+                size: data.len() as u64, 
+                blocks: 0, 
+                atime: SystemTime::now(), 
+                mtime: SystemTime::now(), 
+                ctime: SystemTime::now(), 
+                crtime: SystemTime::now(), 
+                kind: fuser::FileType::RegularFile, 
+                perm: mode as u16, 
+                nlink: 0, 
+                uid: *UID, 
+                gid: *GID, 
+                rdev: 0, 
+                blksize: 0, 
+                flags: 0,
+            },
+            name: OsString::from_str(name).unwrap(),
+            data,
+            trigger: trigger.into(),
+        }
+    }
+}
+
+impl File for DeleteFile {
+    fn name(&self) -> &OsStr {
+        &self.name
+    }
+
+    fn attr(&self) -> &FileAttr {
+        &self.attr
+    }
+
+    fn setattr(
+        &mut self, 
+        _mode: Option<u32>,
+        _uid: Option<u32>,
+        _gid: Option<u32>,
+        _size: Option<u64>,
+        _fh: Option<u64>,
+        _flags: Option<u32>,
+    ) -> Result<(), c_int> {
+        Err(PERMISSION_DENIED)
+    }
+    
+    fn rename(&mut self, _new_name: &OsStr, _in_user_dir: bool) -> Result<(), c_int> {
+        Err(PERMISSION_DENIED)
+    }
+    
+    fn read(&mut self, offset: i64, size: u32, _flags: i32) -> Result<&[u8], c_int> {
+        read(&self.data, offset, size)
+    }
+    
+    fn write(&mut self, _offset: i64, _data: &[u8], _write_flags: u32, _flags: i32) -> Result<u32, c_int> {
+        Err(PERMISSION_DENIED)
+    }
+    
+    fn delete(&mut self) -> Result<(), c_int> {
+        self.trigger.fire();
+        Ok(())
+    }
+
+    fn replace_text(&mut self, old: &[u8], new: &[u8]) -> bool {
+        replace_text(&mut self.data, &mut self.attr, old, new)
+    }
+}
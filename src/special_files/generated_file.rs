@@ -0,0 +1,95 @@
+use std::{ffi::{c_int, OsStr, OsString}, str::FromStr, time::SystemTime};
+
+use fuser::{consts::FOPEN_DIRECT_IO, FileAttr};
+
+use crate::{errors::PERMISSION_DENIED, file_helpers::read, files::File, main_fs::{get_unique_ino, GID, UID}};
+
+/// Read only file showing what `render` returns, rebuilt each time it is opened or read from
+/// the start. `.progress` and `Scoreboard` are these.
+#[derive(Debug)]
+pub struct GeneratedFile {
+    pub attr: FileAttr,
+    pub name: OsString,
+    pub render: fn() -> String,
+    pub data: Vec<u8>,
+}
+
+impl GeneratedFile {
+    pub fn new(name: &str, render: fn() -> String) -> Self {
+        Self {
+            attr: FileAttr { 
+                ino: get_unique_ino(), 
+                size: 0, 
+                blocks: 0, 
+                atime: SystemTime::now(), 
+                mtime: SystemTime::now(), 
+                ctime: SystemTime::now(), 
+                crtime: SystemTime::now(), 
+                kind: fuser::FileType::RegularFile, 
+                perm: 0o444, 
+                nlink: 0, 
+                uid: *UID, 
+                gid: *GID, 
+                rdev: 0, 
+                blksize: 0, 
+                flags: 0,
+            },
+            name: OsString::from_str(name).unwrap(),
+            render,
+            data: Vec::new(),
+        }
+    }
+
+    fn refresh(&mut self) {
+        self.data = (self.render)().into_bytes();
+        self.attr.size = self.data.len() as u64;
+        self.attr.mtime = SystemTime::now();
+    }
+}
+
+impl File for GeneratedFile {
+    fn name(&self) -> &OsStr {
+        &self.name
+    }
+
+    fn attr(&self) -> &FileAttr {
+        &self.attr
+    }
+
+    fn setattr(
+        &mut self, 
+        _mode: Option<u32>,
+        _uid: Option<u32>,
+        _gid: Option<u32>,
+        _size: Option<u64>,
+        _fh: Option<u64>,
+        _flags: Option<u32>,
+    ) -> Result<(), c_int> {
+        Err(PERMISSION_DENIED)
+    }
+    
+    fn rename(&mut self, _new_name: &OsStr, _in_user_dir: bool) -> Result<(), c_int> {
+        Err(PERMISSION_DENIED)
+    }
+    
+    fn read(&mut self, offset: i64, size: u32, _flags: i32) -> Result<&[u8], c_int> {
+        if offset == 0 {
+            self.refresh();
+        }
+        read(&self.data, offset, size)
+    }
+    
+    fn write(&mut self, _offset: i64, _data: &[u8], _write_flags: u32, _flags: i32) -> Result<u32, c_int> {
+        Err(PERMISSION_DENIED)
+    }
+    
+    fn delete(&mut self) -> Result<(), c_int> {
+        Err(PERMISSION_DENIED)
+    }
+
+    fn open(&mut self, _flags: i32) -> Result<u32, c_int> {
+        self.refresh();
+        // The size changes between reads, so skip the page cache.
+        Ok(FOPEN_DIRECT_IO)
+    }
+}
@@ -1,3 +1,11 @@
 pub mod trigger_file;
 
-pub mod text_file;
\ No newline at end of file
+pub mod text_file;
+pub mod open_count_file;
+pub mod order_file;
+pub mod delete_file;
+pub mod progress_file;
+pub mod hint_file;
+pub mod victory_file;
+pub mod command_file;
+pub mod generated_file;
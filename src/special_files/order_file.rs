@@ -0,0 +1,140 @@
+use std::{ffi::{c_int, OsStr, OsString}, str::FromStr, sync::{Arc, Mutex}, time::SystemTime};
+
+use fuser::FileAttr;
+
+use crate::{errors::PERMISSION_DENIED, file_helpers::read, files::File, main_fs::{GID, UID}, module_state};
+
+use super::trigger_file::Trigger;
+
+/// Progress shared by every file of one ordering puzzle.
+#[derive(Debug)]
+pub struct OrderState {
+    pub len: usize,
+    pub pos: usize,
+    pub trigger: Trigger,
+    pub on_wrong: Trigger,
+    /// Module and key in `module_state` that keep `pos` across restarts.
+    pub saved_as: Option<(String, String)>,
+}
+
+impl OrderState {
+    pub fn new(trigger: impl Into<Trigger>, on_wrong: impl Into<Trigger>, len: usize) -> Arc<Mutex<Self>> {
+        Arc::new(Mutex::new(Self { len, pos: 0, trigger: trigger.into(), on_wrong: on_wrong.into(), saved_as: None }))
+    }
+
+    /// Picks up at the position saved under `key`, and saves every step there.
+    pub fn saved(module: &str, key: &str, trigger: impl Into<Trigger>, on_wrong: impl Into<Trigger>, len: usize) -> Arc<Mutex<Self>> {
+        let pos = module_state::get_u64(module, key, 0) as usize;
+        let saved_as = Some((module.to_string(), key.to_string()));
+        Arc::new(Mutex::new(Self { len, pos, trigger: trigger.into(), on_wrong: on_wrong.into(), saved_as }))
+    }
+
+    fn save(&self) {
+        if let Some((module, key)) = &self.saved_as {
+            module_state::set(module, key, self.pos.into());
+        }
+    }
+
+    /// Positions start at 0. Reading the wrong file starts the sequence over.
+    pub fn visit(&mut self, position: usize) -> bool {
+        if self.pos >= self.len {
+            return true;
+        }
+
+        if self.pos == position {
+            self.pos += 1;
+            self.save();
+            if self.pos == self.len {
+                self.trigger.fire();
+            }
+            true
+        } else {
+            self.pos = 0;
+            self.save();
+            self.on_wrong.fire();
+            false
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct OrderFile {
+    pub attr: FileAttr,
+    pub name: OsString,
+    pub position: usize,
+    pub state: Arc<Mutex<OrderState>>,
+    pub right: Vec<u8>,
+    pub wrong: Vec<u8>,
+}
+
+impl OrderFile {
+    pub fn new(state: Arc<Mutex<OrderState>>, position: usize, name: &str, right: Vec<u8>, wrong: Vec<u8>, ino: u64, mode: u32) -> Self {
+        Self {
+            attr: FileAttr { 
+                ino, 
+                size: right.len().max(wrong.len()) as u64, 
+                blocks: 0, 
+                atime: SystemTime::now(), 
+                mtime: SystemTime::now(), 
+                ctime: SystemTime::now(), 
+                crtime: SystemTime::now(), 
+                kind: fuser::FileType::RegularFile, 
+                perm: mode as u16, 
+                nlink: 0, 
+                uid: *UID, 
+                gid: *GID, 
+                rdev: 0, 
+                blksize: 0, 
+                flags: 0,
+            },
+            name: OsString::from_str(name).unwrap(),
+            position,
+            state,
+            right,
+            wrong,
+        }
+    }
+}
+
+impl File for OrderFile {
+    fn name(&self) -> &OsStr {
+        &self.name
+    }
+
+    fn attr(&self) -> &FileAttr {
+        &self.attr
+    }
+
+    fn setattr(
+        &mut self, 
+        _mode: Option<u32>,
+        _uid: Option<u32>,
+        _gid: Option<u32>,
+        _size: Option<u64>,
+        _fh: Option<u64>,
+        _flags: Option<u32>,
+    ) -> Result<(), c_int> {
+        Err(PERMISSION_DENIED)
+    }
+    
+    fn rename(&mut self, _new_name: &OsStr, _in_user_dir: bool) -> Result<(), c_int> {
+        Err(PERMISSION_DENIED)
+    }
+    
+    fn read(&mut self, offset: i64, size: u32, _flags: i32) -> Result<&[u8], c_int> {
+        let data = if self.state.lock().unwrap().visit(self.position) {
+            &self.right
+        } else {
+            &self.wrong
+        };
+        read(data, offset, size)
+    }
+    
+    fn write(&mut self, _offset: i64, _data: &[u8], _write_flags: u32, _flags: i32) -> Result<u32, c_int> {
+        Err(PERMISSION_DENIED)
+    }
+    
+    fn delete(&mut self) -> Result<(), c_int> {
+        Err(PERMISSION_DENIED)
+    }
+}
@@ -0,0 +1,65 @@
+use std::fmt::Write;
+
+use users::get_user_by_uid;
+
+use crate::{main_fs::SHARED_PLAYER, messages::Message, modules::registry, progress::{self, Intro}};
+
+pub const PROGRESS_FILE: &str = ".progress";
+
+const MODULE: Message = Message::new("progress", "module");
+const STATUS: Message = Message::new("progress", "status");
+const LEVEL: Message = Message::new("progress", "level");
+const TIME: Message = Message::new("progress", "time");
+const ATTEMPTS: Message = Message::new("progress", "attempts");
+const HINTS: Message = Message::new("progress", "hints");
+const SOLVED: Message = Message::new("progress", "solved");
+const IN_PROGRESS: Message = Message::new("progress", "in_progress");
+const LOCKED: Message = Message::new("progress", "locked");
+const INTRO: Message = Message::new("progress", "intro");
+const SCOREBOARD: Message = Message::new("progress", "scoreboard");
+const PLAYER: Message = Message::new("progress", "player");
+const SOLVED_COUNT: Message = Message::new("progress", "solved_count");
+pub const MESSAGES: &[Message] = &[MODULE, STATUS, LEVEL, TIME, ATTEMPTS, HINTS, SOLVED, IN_PROGRESS, LOCKED, INTRO, SCOREBOARD, PLAYER, SOLVED_COUNT];
+
+fn duration(secs: u64) -> String {
+    format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+pub fn user_name(uid: u32) -> String {
+    get_user_by_uid(uid)
+        .map(|user| user.name().to_string_lossy().to_string())
+        .unwrap_or_else(|| uid.to_string())
+}
+
+pub fn render() -> String {
+    let progress = progress::snapshot();
+    let mut out = String::new();
+
+    writeln!(out, "{:<20} {:<12} {:<8} {:<10} {:<10} {}", MODULE.text(), STATUS.text(), LEVEL.text(), TIME.text(), ATTEMPTS.text(), HINTS.text()).unwrap();
+    for module in registry::modules() {
+        let state = progress.modules.get(&module.id).cloned().unwrap_or_default();
+        let (status, time) = match (state.started, state.solved) {
+            (_, Some(solved)) => (SOLVED.text(), duration(solved.saturating_sub(state.started.unwrap_or(solved)))),
+            (Some(started), None) => (IN_PROGRESS.text(), duration(progress::now().saturating_sub(started))),
+            (None, None) => (LOCKED.text(), "-".to_string()),
+        };
+        let difficulty = state.difficulty.unwrap_or_else(|| registry::configured_difficulty(&module.id));
+        writeln!(out, "{:<20} {:<12} {:<8} {:<10} {:<10} {}/{}", module.dir, status, difficulty, time, state.attempts, state.hints, registry::hints(&module).len()).unwrap();
+    }
+
+    if progress.intro != Intro::Done {
+        writeln!(out, "\n{}", INTRO.text()).unwrap();
+    }
+
+    let mut players: Vec<_> = progress::all().into_iter().filter(|(uid, _)| *uid != SHARED_PLAYER).collect();
+    if players.len() > 1 {
+        players.sort_by_key(|(uid, player)| (std::cmp::Reverse(player.solved()), player.attempts(), *uid));
+
+        writeln!(out, "\n{}", SCOREBOARD.text()).unwrap();
+        writeln!(out, "{:<20} {:<8} {:<10} {}", PLAYER.text(), SOLVED_COUNT.text(), ATTEMPTS.text(), HINTS.text()).unwrap();
+        for (uid, player) in players {
+            writeln!(out, "{:<20} {:<8} {:<10} {}", user_name(uid), player.solved(), player.attempts(), player.hints()).unwrap();
+        }
+    }
+    out
+}
@@ -1,8 +1,35 @@
-use std::{ffi::{c_int, OsStr, OsString}, fmt::Debug, str::FromStr, time::SystemTime};
+use std::{ffi::{c_int, OsStr, OsString}, fmt::Debug, str::FromStr, sync::Arc, time::SystemTime};
 
 use fuser::FileAttr;
 
-use crate::{errors::{NOT_SUPPORTED, PERMISSION_DENIED}, file_helpers::read, files::File, main_fs::{GID, UID}};
+use crate::{errors::{NOT_SUPPORTED, PERMISSION_DENIED}, file_helpers::{read, replace_text}, files::File, main_fs::{GID, UID}};
+
+/// Callback run when a special file fires. Plain `fn()` items convert into it,
+/// so modules can keep passing function names while packs pass closures.
+#[derive(Clone)]
+pub struct Trigger(Arc<dyn Fn() + Send + Sync>);
+
+impl Trigger {
+    pub fn new(trigger: impl Fn() + Send + Sync + 'static) -> Self {
+        Self(Arc::new(trigger))
+    }
+
+    pub fn fire(&self) {
+        (self.0)()
+    }
+}
+
+impl<F: Fn() + Send + Sync + 'static> From<F> for Trigger {
+    fn from(trigger: F) -> Self {
+        Self::new(trigger)
+    }
+}
+
+impl Debug for Trigger {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Trigger")
+    }
+}
 
 #[derive(Debug)]
 pub struct TriggerFile {
@@ -10,11 +37,11 @@ pub struct TriggerFile {
     pub name: OsString,
     pub data: Vec<u8>,
     pub triggered: bool,
-    pub trigger: fn() -> (),
+    pub trigger: Trigger,
 }
 
 impl TriggerFile {
-    pub fn new(trigger: fn() -> (), name: &str, data: Vec<u8>, ino: u64, mode: u32, flags: u32) -> Self {
+    pub fn new(trigger: impl Into<Trigger>, name: &str, data: Vec<u8>, ino: u64, mode: u32, flags: u32) -> Self {
         Self {
             attr: FileAttr { 
                 ino, 
@@ -36,7 +63,7 @@ impl TriggerFile {
             name: OsString::from_str(name).unwrap(),
             data,
             triggered: false,
-            trigger,
+            trigger: trigger.into(),
         }
     }
 }
@@ -69,7 +96,7 @@ impl File for TriggerFile {
     fn read(&mut self, offset: i64, size: u32, flags: i32) -> Result<&[u8], c_int> {
         let out = read(&self.data, offset, size);
         if out.is_ok() && !self.triggered {
-            (self.trigger)();
+            self.trigger.fire();
             self.triggered = true;
         }
         out
@@ -82,4 +109,8 @@ impl File for TriggerFile {
     fn delete(&mut self) -> Result<(), c_int> {
         Err(PERMISSION_DENIED)
     }
-}
\ No newline at end of file
+
+    fn replace_text(&mut self, old: &[u8], new: &[u8]) -> bool {
+        replace_text(&mut self.data, &mut self.attr, old, new)
+    }
+}
@@ -0,0 +1,98 @@
+use std::{ffi::{c_int, OsStr, OsString}, str::FromStr, time::SystemTime};
+
+use fuser::{consts::FOPEN_DIRECT_IO, FileAttr};
+
+use crate::{errors::PERMISSION_DENIED, file_helpers::read, files::File, flags, main_fs::{current_player, get_unique_ino, GID, UID}, messages::Message};
+
+const VICTORY: Message = Message::new("victory", "file");
+pub const MESSAGES: &[Message] = &[VICTORY];
+
+/// Shows the flag of the player reading it, so shared rooms hand out the right flag to everyone.
+#[derive(Debug)]
+pub struct VictoryFile {
+    pub attr: FileAttr,
+    pub name: OsString,
+    pub module: String,
+    pub data: Vec<u8>,
+}
+
+impl VictoryFile {
+    pub fn new(module: &str) -> Self {
+        let mut file = Self {
+            attr: FileAttr { 
+                ino: get_unique_ino(), 
+                size: 0, 
+                blocks: 0, 
+                atime: SystemTime::now(), 
+                mtime: SystemTime::now(), 
+                ctime: SystemTime::now(), 
+                crtime: SystemTime::now(), 
+                kind: fuser::FileType::RegularFile, 
+                perm: 0o444, 
+                nlink: 0, 
+                uid: *UID, 
+                gid: *GID, 
+                rdev: 0, 
+                blksize: 0, 
+                flags: 0,
+            },
+            name: OsString::from_str("Victory").unwrap(),
+            module: module.to_string(),
+            data: Vec::new(),
+        };
+        file.refresh();
+        file
+    }
+
+    fn refresh(&mut self) {
+        let flag = flags::flag(&self.module, current_player());
+        self.data = VICTORY.format(&[("flag", &flag)]).into_bytes();
+        self.attr.size = self.data.len() as u64;
+    }
+}
+
+impl File for VictoryFile {
+    fn name(&self) -> &OsStr {
+        &self.name
+    }
+
+    fn attr(&self) -> &FileAttr {
+        &self.attr
+    }
+
+    fn setattr(
+        &mut self, 
+        _mode: Option<u32>,
+        _uid: Option<u32>,
+        _gid: Option<u32>,
+        _size: Option<u64>,
+        _fh: Option<u64>,
+        _flags: Option<u32>,
+    ) -> Result<(), c_int> {
+        Err(PERMISSION_DENIED)
+    }
+    
+    fn rename(&mut self, _new_name: &OsStr, _in_user_dir: bool) -> Result<(), c_int> {
+        Err(PERMISSION_DENIED)
+    }
+    
+    fn read(&mut self, offset: i64, size: u32, _flags: i32) -> Result<&[u8], c_int> {
+        if offset == 0 {
+            self.refresh();
+        }
+        read(&self.data, offset, size)
+    }
+    
+    fn write(&mut self, _offset: i64, _data: &[u8], _write_flags: u32, _flags: i32) -> Result<u32, c_int> {
+        Err(PERMISSION_DENIED)
+    }
+    
+    fn delete(&mut self) -> Result<(), c_int> {
+        Err(PERMISSION_DENIED)
+    }
+
+    fn open(&mut self, _flags: i32) -> Result<u32, c_int> {
+        self.refresh();
+        Ok(FOPEN_DIRECT_IO)
+    }
+}
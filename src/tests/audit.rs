@@ -0,0 +1,35 @@
+use crate::{audit::{self, Record}, main_fs::with_player, ops, sim::Sim};
+
+#[test]
+fn written_bytes_survive_the_log() {
+    let record = Record { op: "write".to_string(), write_flags: Some(1), data: Some(vec![0xff, 0, b'a']), ..Record::default() };
+    let line = serde_json::to_string(&record).unwrap();
+    let read: Record = serde_json::from_str(&line).unwrap();
+    assert_eq!(read.data, Some(vec![0xff, 0, b'a']));
+    assert_eq!(read.write_flags, Some(1));
+}
+
+#[test]
+fn paths_follow_renames() {
+    let sim = Sim::player();
+    sim.mkdir("_audit", 0o755).unwrap();
+    let ino = sim.create("_audit/f", 0o644).unwrap().ino;
+    let path_of = || with_player(sim.request.uid, || ops::path_of(ino));
+    assert_eq!(path_of().as_deref(), Some("/_audit/f"));
+
+    sim.rename("_audit", "_moved").unwrap();
+    assert_eq!(path_of().as_deref(), Some("/_moved/f"));
+    sim.rename("_moved/f", "_moved/g").unwrap();
+    assert_eq!(path_of().as_deref(), Some("/_moved/g"));
+}
+
+#[test]
+fn replays_take_the_seed_of_the_first_session() {
+    let session = |seed| Record { op: "session".to_string(), seed: Some(seed), ..Record::default() };
+    let write = Record { op: "write".to_string(), ..Record::default() };
+    let records: Vec<Record> = [write, session(7), session(8)].iter()
+        .map(|record| serde_json::from_str(&serde_json::to_string(record).unwrap()).unwrap())
+        .collect();
+    assert_eq!(audit::seed(&records), Some(7));
+    assert_eq!(audit::seed(&records[..1]), None);
+}
@@ -0,0 +1,80 @@
+use crate::{background_tasks::add_file, control_socket::{run, send, serve}, file_helpers::text_file, harness, modules::registry::{self, Module}, sim::Sim};
+
+fn player() -> (Sim, u32) {
+    let sim = Sim::after_intro();
+    (sim, sim.request.uid)
+}
+
+#[test]
+fn files_can_be_added_listed_and_removed() {
+    let (sim, uid) = player();
+    assert!(run(&format!("add {uid} _notes/deep Note  two  spaces\\nand a line")).is_ok());
+    assert_eq!(sim.read_file("_notes/deep/Note").unwrap(), "two  spaces\nand a line\n");
+    assert!(run(&format!("add {uid} _notes/deep Note again")).unwrap_err().contains("already exists"));
+
+    let tree = run(&format!("tree {uid} _notes")).unwrap();
+    assert!(tree.contains("deep/ "), "{tree}");
+    assert!(tree.contains("  Note "), "{tree}");
+
+    assert!(run(&format!("rm {uid} _notes")).is_ok());
+    assert!(!sim.exists("_notes"));
+    assert!(run(&format!("rm {uid} _notes")).is_err());
+}
+
+#[test]
+fn removing_a_shared_directory_only_affects_one_player() {
+    registry::register(Module::new("test_rm_shared", "Test_Rm_Shared", || {
+        add_file("Test_Rm_Shared", text_file("Board", "for everyone"));
+    }).shared(true));
+    let (one, other) = (Sim::player(), Sim::player());
+    one.start("test_rm_shared");
+    other.start("test_rm_shared");
+    assert!(one.wait_for("Test_Rm_Shared/Board") && other.wait_for("Test_Rm_Shared/Board"));
+
+    let uid = one.request.uid;
+    assert!(run(&format!("rm {uid} Test_Rm_Shared/Board")).unwrap_err().contains("shared"));
+    assert!(one.exists("Test_Rm_Shared/Board"));
+    assert!(run(&format!("rm {uid} Test_Rm_Shared")).is_ok());
+    assert!(!one.exists("Test_Rm_Shared"));
+    assert_eq!(other.read_file("Test_Rm_Shared/Board").unwrap(), "for everyone");
+}
+
+#[test]
+fn modules_can_be_solved_and_reset_for_a_player() {
+    let (sim, uid) = player();
+    assert!(run(&format!("solve {uid} The_Door")).is_ok());
+    assert!(sim.exists("The_Door/Victory"));
+    assert!(run(&format!("solve {uid} The_Door")).unwrap_err().contains("already solved"));
+    assert!(run(&format!("status {uid}")).unwrap().lines().any(|line| line.starts_with("The_Door") && line.contains("solved")));
+
+    assert!(run(&format!("reset {uid} The_Door")).is_ok());
+    assert!(harness::wait_until(|| !sim.exists("The_Door/Victory")));
+}
+
+#[test]
+fn locked_modules_can_be_unlocked() {
+    let (sim, uid) = player();
+    assert!(sim.exists("Final_Room/Locked"));
+    assert!(run(&format!("unlock {uid} final")).is_ok());
+    assert!(!sim.exists("Final_Room/Locked"));
+    assert!(sim.exists("Final_Room/Trophy"));
+    assert!(run(&format!("unlock {uid} final")).unwrap_err().contains("already open"));
+}
+
+#[test]
+fn bad_commands_are_errors() {
+    assert!(run("solve 4000000001 The_Door").unwrap_err().contains("no player"));
+    assert!(run("solve nobody The_Door").unwrap_err().contains("not a uid"));
+    assert!(run("solve").unwrap_err().contains("wrong arguments"));
+    assert!(run("dance").unwrap_err().contains("unknown command"));
+    assert!(run("players").unwrap().starts_with("Uid"));
+}
+
+#[test]
+fn the_socket_answers_commands() {
+    let path = harness::dir().join("control.sock");
+    serve(&path).unwrap();
+    assert!(serve(&path).is_err());
+    assert_eq!(send(&path, ["help".to_string(), "players".to_string()]), Ok(true));
+    assert_eq!(send(&path, ["dance".to_string()]), Ok(false));
+}
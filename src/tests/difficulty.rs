@@ -0,0 +1,79 @@
+use crate::{background_tasks::add_file, difficulty::Difficulty, file_helpers::text_file, main_fs::with_player, modules::{classroom, correct_order, kill_them_all, many_open, registry::{self, Module}}, progress, sim::Sim};
+
+#[test]
+fn difficulties_parse_from_names_and_numbers() {
+    assert_eq!("easy".parse(), Ok(Difficulty::Easy));
+    assert_eq!("2".parse(), Ok(Difficulty::Normal));
+    assert_eq!("Hard".parse(), Ok(Difficulty::Hard));
+    assert!("extreme".parse::<Difficulty>().is_err());
+    assert_eq!(Difficulty::Hard.to_string(), "hard");
+}
+
+#[test]
+fn difficulty_can_be_set_per_module() {
+    // Other tests wait for every module to show up after the intro.
+    registry::register(Module::new("test_difficulty", "Test_Difficulty", || {
+        add_file("Test_Difficulty", text_file("Nothing", ""));
+    }));
+    assert!(registry::set_difficulty(&["Test_Difficulty=extreme".to_string()]).is_err());
+    assert!(registry::set_difficulty(&["nowhere=hard".to_string()]).is_err());
+    assert_eq!(registry::configured_difficulty("test_difficulty"), Difficulty::Normal);
+    registry::set_difficulty(&["Test_Difficulty=3".to_string()]).unwrap();
+    assert_eq!(registry::configured_difficulty("test_difficulty"), Difficulty::Hard);
+}
+
+#[test]
+fn counts_scale_with_difficulty() {
+    let easy = Sim::player();
+    easy.start_at(many_open::ID, Difficulty::Easy);
+    let hard = Sim::player();
+    hard.start_at(many_open::ID, Difficulty::Hard);
+    assert!((3..=5).contains(&with_player(easy.request.uid, many_open::opens_needed)));
+    assert!((25..=40).contains(&with_player(hard.request.uid, many_open::opens_needed)));
+
+    let sim = Sim::player();
+    sim.start_at(correct_order::ID, Difficulty::Easy);
+    assert!(sim.wait_for("Broken_Sorter/4"));
+    assert_eq!(sim.readdir("Broken_Sorter").unwrap().len(), 5);
+    assert_eq!(with_player(sim.request.uid, correct_order::order).len(), 4);
+}
+
+#[test]
+fn hard_sorter_has_a_time_limit() {
+    let sim = Sim::player();
+    sim.start_at(correct_order::ID, Difficulty::Hard);
+    assert!(sim.wait_for("Broken_Sorter/4"));
+    sim.pause_clock();
+    let order = with_player(sim.request.uid, correct_order::order);
+    let read = |file: usize| sim.read_file(&format!("Broken_Sorter/{file}")).unwrap();
+    assert_eq!(read(order[0]), "Yes\n");
+    sim.advance(correct_order::TIME_LIMIT);
+    assert_eq!(read(order[1]), "No \n");
+    assert_eq!(with_player(sim.request.uid, progress::snapshot).modules[correct_order::ID].attempts, 2);
+}
+
+#[test]
+fn hard_arena_respawns_ogres() {
+    let sim = Sim::player();
+    sim.start_at(kill_them_all::ID, Difficulty::Hard);
+    sim.pause_clock();
+    let ogres = with_player(sim.request.uid, kill_them_all::num_ogres) as usize;
+    let count = || sim.readdir("Arena").unwrap().iter().filter(|x| *x == "Ogre").count();
+    assert!(sim.wait_for("Arena/Ogre"));
+    sim.unlink("Arena/Ogre").unwrap();
+    assert_eq!(count(), ogres - 1);
+    sim.advance(kill_them_all::RESPAWN);
+    assert_eq!(count(), ogres);
+}
+
+#[test]
+fn hints_and_progress_follow_the_difficulty() {
+    let module = registry::get(classroom::ID).unwrap();
+    for (difficulty, hints) in [(Difficulty::Easy, 3), (Difficulty::Normal, 2), (Difficulty::Hard, 1)] {
+        let sim = Sim::player();
+        sim.start_at(classroom::ID, difficulty);
+        assert_eq!(with_player(sim.request.uid, || registry::hints(&module).len()), hints);
+        let line = sim.read_file(".progress").unwrap().lines().find(|x| x.starts_with("Classroom")).unwrap().to_string();
+        assert!(line.contains(&difficulty.to_string()), "{line}");
+    }
+}
@@ -0,0 +1,64 @@
+use std::fs;
+
+use crate::{background_tasks::flush, main_fs::with_player, messages::{self, Message}, modules::classroom, sim::Sim};
+
+use super::packs::pack_dir;
+
+#[test]
+fn shipped_catalogs_are_complete() {
+    assert_eq!(messages::check("messages").unwrap(), Vec::<String>::new());
+}
+
+#[test]
+fn missing_keys_are_flagged() {
+    let dir = pack_dir("test_messages", &[("xx.toml", "[classroom]\nteacher = \"?\\n\"\nprincipal = \"!\\n\"\n")]);
+    let problems = messages::check(&dir.to_string_lossy()).unwrap();
+    assert!(problems.contains(&"xx: missing classroom.sally, shown in English".to_string()), "{problems:?}");
+    assert!(problems.contains(&"xx: orphaned classroom.principal".to_string()), "{problems:?}");
+    assert!(!problems.iter().any(|x| x.starts_with("en:")), "{problems:?}");
+
+    fs::write(dir.join("xx.toml"), "[classroom\n").unwrap();
+    assert!(messages::check(&dir.to_string_lossy()).is_err());
+}
+
+#[test]
+fn missing_text_falls_back_to_english() {
+    let sally = Message::new(classroom::ID, "sally");
+    assert_eq!(messages::text_in("nowhere", sally), messages::text_in("en", sally));
+    assert!(messages::text_in("en", sally).contains("YEAYYY"));
+}
+
+#[test]
+fn switching_locale_swaps_text() {
+    messages::install(messages::load_dir("messages").unwrap());
+    let sim = Sim::player();
+    sim.start(classroom::ID);
+    assert!(sim.wait_for("Classroom/Teacher"));
+    assert!(sim.read_file("Classroom/Teacher").unwrap().contains("looking for Sally"));
+
+    sim.write_file(".control", "locale de\n").unwrap();
+    assert!(sim.read_file(".control").unwrap().contains("Switched to de"));
+    flush();
+    assert!(sim.read_file("Classroom/Teacher").unwrap().contains("Ich suche Sally"));
+    assert!(sim.read_file("Welcome").unwrap().contains("Hallo und willkommen"));
+    assert_eq!(with_player(sim.request.uid, messages::locale), "de");
+
+    sim.write_file(".control", "locale xx\n").unwrap();
+    assert!(sim.read_file(".control").unwrap().contains("Fehler: no messages for xx"));
+}
+
+#[test]
+fn hints_and_status_pages_follow_the_locale() {
+    messages::install(messages::load_dir("messages").unwrap());
+    let sim = Sim::player();
+    sim.start(classroom::ID);
+    sim.write_file(".control", "locale de\n").unwrap();
+    sim.write_file(".control", "hint classroom\n").unwrap();
+    assert!(sim.wait_for("Classroom/Hint"));
+    let hint = sim.read_file("Classroom/Hint").unwrap();
+    assert!(hint.starts_with("Tipp 1: Sally taucht nur auf"), "{hint}");
+    assert!(hint.contains("Weitere Tipps"), "{hint}");
+    assert!(sim.read_file(".progress").unwrap().starts_with("Modul "));
+    assert!(sim.read_file("Submit").unwrap().contains("Flagge"));
+    assert!(sim.read_file(".control").unwrap().starts_with("Schreib einen Befehl"));
+}
@@ -0,0 +1,26 @@
+// `modules` and `posix` run against a real mount, see `crate::harness`, and are skipped where
+// FUSE is not available, unless `HACK_REQUIRE_FUSE` is set. The others go through `crate::sim`
+// and need no kernel.
+
+use std::io;
+
+mod audit;
+mod control_socket;
+mod modules;
+mod difficulty;
+mod messages;
+mod packs;
+mod posix;
+mod progression;
+mod random;
+mod save;
+mod scheduler;
+mod scoreboard;
+mod shutdown;
+mod sim;
+mod tasks;
+
+/// Error code of a failed io call, to compare against the constants in `errors`.
+pub fn errno<T>(result: io::Result<T>) -> Option<i32> {
+    result.err().and_then(|e| e.raw_os_error())
+}
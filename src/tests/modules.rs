@@ -0,0 +1,104 @@
+use std::fs::{self, File};
+
+use crate::{errors::{FILE_NOT_FOUND, PERMISSION_DENIED}, flags, harness::{mount, Mount}, main_fs::with_player, modules::{classroom, correct_order, kill_them_all, many_open}, scoreboard::SUBMIT_FILE};
+
+use super::errno;
+
+/// Waits for the module's `Victory` file and checks that it holds a valid flag for us.
+fn assert_victory(mount: &Mount, dir: &str, id: &str) -> String {
+    let victory = format!("{dir}/Victory");
+    assert!(mount.wait_for(&victory), "{victory} never showed up");
+    let text = mount.read(&victory).unwrap();
+    let flag = text.split_whitespace().find(|x| x.starts_with("FLAG{")).expect("no flag in the Victory file").to_string();
+    let (module, uid) = flags::verify(&flag).unwrap();
+    assert_eq!(module, id);
+    assert_eq!(uid, unsafe { libc::getuid() });
+    flag
+}
+
+/// Runs `f` as the player the tests mount as.
+fn as_us<T>(f: impl FnOnce() -> T) -> T {
+    with_player(unsafe { libc::getuid() }, f)
+}
+
+#[test]
+fn the_door_needs_enough_openers() {
+    let Some(mount) = mount() else { return };
+    let needed = as_us(many_open::opens_needed) as usize;
+    let door = mount.path("The_Door/Heavy_Door");
+    assert!(mount.read("The_Door/Heavy_Door").unwrap().contains(&format!("You would need {needed} men")));
+    assert_eq!(errno(mount.write("The_Door/Heavy_Door", "push")), Some(PERMISSION_DENIED));
+
+    let handles: Vec<File> = (0..needed).map(|_| File::open(&door).unwrap()).collect();
+    assert!(fs::read_to_string(&door).unwrap().contains(&format!("Wow you actually got {needed} men")));
+    drop(handles);
+    assert_victory(mount, many_open::BASE_PATH, many_open::ID);
+}
+
+#[test]
+fn sally_comes_back_for_reading_and_writing() {
+    let Some(mount) = mount() else { return };
+    assert_eq!(errno(mount.read("Classroom/Sally")), Some(FILE_NOT_FOUND));
+    assert!(mount.read("Classroom/Teacher").unwrap().contains("Sally"));
+
+    let mut sally = File::options().read(true).write(true).open(mount.path("Classroom/Sally")).unwrap();
+    let mut text = String::new();
+    std::io::Read::read_to_string(&mut sally, &mut text).unwrap();
+    assert!(text.contains("YEAYYY"));
+    assert_victory(mount, classroom::BASE_PATH, classroom::ID);
+}
+
+#[test]
+fn broken_sorter_wants_the_right_order() {
+    let Some(mount) = mount() else { return };
+    let order = as_us(correct_order::order);
+    let never = (0..8).find(|x| !order.contains(x)).unwrap();
+    let read = |file: usize| mount.read(&format!("Broken_Sorter/{file}")).unwrap();
+    assert_eq!(read(order[0]), "Yes\n");
+    assert_eq!(read(never), "No \n");
+    for file in &order[..order.len() - 1] {
+        assert_eq!(read(*file), "Yes\n", "reading {file}");
+    }
+    assert!(!mount.exists("Broken_Sorter/Victory"));
+    assert_eq!(read(order[order.len() - 1]), "Yes\n");
+    assert_victory(mount, correct_order::BASE_PATH, correct_order::ID);
+}
+
+#[test]
+fn arena_is_won_when_every_ogre_is_gone() {
+    let Some(mount) = mount() else { return };
+    assert!(mount.read("Arena/Warrior").unwrap().contains("Ogres"));
+    assert_eq!(errno(fs::remove_file(mount.path("Arena/Warrior"))), Some(PERMISSION_DENIED));
+
+    let mut killed = 0;
+    while fs::remove_file(mount.path("Arena/Ogre")).is_ok() {
+        killed += 1;
+    }
+    assert_eq!(killed, as_us(kill_them_all::num_ogres));
+    assert_victory(mount, kill_them_all::BASE_PATH, kill_them_all::ID);
+}
+
+#[test]
+fn library_books_are_read_in_order() {
+    let Some(mount) = mount() else { return };
+    assert!(mount.read("Library/Librarian").unwrap().contains("cats, then dogs, then birds"));
+    for book in ["Cats", "Dogs", "Birds"] {
+        mount.read(&format!("Library/Shelf/{book}")).unwrap();
+    }
+    assert_victory(mount, "Library", "library");
+}
+
+#[test]
+fn vault_opens_with_the_password() {
+    let Some(mount) = mount() else { return };
+    assert!(mount.read("Vault/Lock").unwrap().contains("The lock is closed"));
+    mount.write("Vault/Lock", "Welcome\n").unwrap();
+    assert!(mount.wait_for("Vault/Gold"));
+    assert!(mount.read("Vault/Lock").unwrap().contains("The lock is open"));
+    let flag = assert_victory(mount, "Vault", "vault");
+
+    mount.write(SUBMIT_FILE, &format!("{flag}\n")).unwrap();
+    assert!(mount.read(SUBMIT_FILE).unwrap().contains("Accepted Vault"));
+    mount.write(SUBMIT_FILE, &format!("{flag}\n")).unwrap();
+    assert!(mount.read(SUBMIT_FILE).unwrap().contains("already submitted"));
+}
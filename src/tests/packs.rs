@@ -0,0 +1,345 @@
+use std::{fs, path::PathBuf};
+
+use crate::{background_tasks::rm_dir, errors::{IO_ERROR, PERMISSION_DENIED}, harness, main_fs::with_player, packs, sim::Sim};
+
+/// Writes pack files into a directory of their own and returns it.
+pub fn pack_dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = harness::dir().join("packs").join(name);
+    fs::create_dir_all(&dir).unwrap();
+    for (file, text) in files {
+        fs::write(dir.join(file), text).unwrap();
+    }
+    dir
+}
+
+fn install(name: &str, text: &str) -> Sim {
+    install_with(name, text, &[])
+}
+
+/// Installs a pack with more files next to it, like its script, and starts it for a new player.
+pub fn install_with(name: &str, text: &str, files: &[(&str, &str)]) -> Sim {
+    let toml = format!("{name}.toml");
+    let dir = pack_dir(name, &[&[(toml.as_str(), text)], files].concat());
+    packs::install(packs::load_dir(&dir.to_string_lossy()).unwrap());
+    let sim = Sim::player();
+    sim.start(name);
+    sim
+}
+
+fn parse_error(text: &str) -> String {
+    let dir = pack_dir("broken", &[]);
+    let path = dir.join(format!("{}.toml", fs::read_dir(&dir).unwrap().count()));
+    fs::write(&path, text).unwrap();
+    packs::parse(&path).unwrap_err()
+}
+
+#[test]
+fn order_group_wins_when_read_in_order() {
+    let sim = install("test_order", r#"
+        id = "test_order"
+        dir = "Test_Order"
+
+        [[files]]
+        path = "Test_Order"
+        name = "First"
+        trigger = { kind = "order", group = "books", position = 1 }
+
+        [[files]]
+        path = "Test_Order"
+        name = "Second"
+        trigger = { kind = "order", group = "books", position = 2, right = "Good\n" }
+
+        [groups.books]
+        then = [{ kind = "victory" }]
+    "#);
+    assert!(sim.wait_for("Test_Order/Second"));
+    assert_eq!(sim.read_file("Test_Order/Second").unwrap(), "No \n");
+    assert_eq!(sim.read_file("Test_Order/First").unwrap(), "Yes\n");
+    assert_eq!(sim.read_file("Test_Order/Second").unwrap(), "Good\n");
+    assert!(sim.wait_for("Test_Order/Victory"));
+}
+
+#[test]
+fn read_once_runs_its_actions() {
+    let sim = install("test_read_once", r#"
+        id = "test_read_once"
+
+        [[files]]
+        path = "test_read_once"
+        name = "Note"
+        content = "Look behind you.\n"
+        trigger = { kind = "read-once", then = [
+            { kind = "add", file = { path = "test_read_once/Behind", name = "Key", content = "A key.\n", count = 2 } },
+            { kind = "remove", path = "test_read_once/Wall" },
+        ] }
+
+        [[files]]
+        path = "test_read_once"
+        name = "Wall"
+        mode = 0o444
+    "#);
+    assert!(sim.wait_for("test_read_once/Wall"));
+    assert_eq!(sim.lookup("test_read_once/Wall").unwrap().perm & 0o777, 0o444);
+    assert_eq!(sim.read_file("test_read_once/Note").unwrap(), "Look behind you.\n");
+    assert!(sim.wait_for("test_read_once/Behind/Key"));
+    assert_eq!(sim.readdir("test_read_once/Behind").unwrap(), ["Key", "Key"]);
+    assert!(harness::wait_until(|| !sim.exists("test_read_once/Wall")));
+    assert!(!sim.exists("test_read_once/Victory"));
+}
+
+#[test]
+fn delete_count_fires_after_the_last_file() {
+    let sim = install("test_delete", r#"
+        id = "test_delete"
+
+        [[files]]
+        path = "test_delete"
+        name = "Rat"
+        count = 3
+        trigger = { kind = "delete-count", group = "rats" }
+
+        [groups.rats]
+        then = [{ kind = "victory" }]
+    "#);
+    assert!(harness::wait_until(|| sim.readdir("test_delete").is_ok_and(|x| x.len() == 3)));
+    sim.unlink("test_delete/Rat").unwrap();
+    sim.unlink("test_delete/Rat").unwrap();
+    assert!(!sim.exists("test_delete/Victory"));
+    sim.unlink("test_delete/Rat").unwrap();
+    assert!(sim.wait_for("test_delete/Victory"));
+    assert_eq!(sim.unlink("test_delete/Victory"), Err(PERMISSION_DENIED));
+}
+
+#[test]
+fn pack_progress_survives_a_rebuild() {
+    let sim = install("test_pack_state", r#"
+        id = "test_pack_state"
+
+        [[files]]
+        path = "test_pack_state"
+        name = "Rat"
+        count = 3
+        trigger = { kind = "delete-count", group = "rats" }
+
+        [[files]]
+        path = "test_pack_state/Shelf"
+        name = "First"
+        trigger = { kind = "order", group = "books", position = 1 }
+
+        [[files]]
+        path = "test_pack_state/Shelf"
+        name = "Second"
+        trigger = { kind = "order", group = "books", position = 2, right = "Good\n" }
+
+        [groups.rats]
+        then = [{ kind = "add", file = { path = "test_pack_state", name = "Cheese" } }]
+
+        [groups.books]
+    "#);
+    assert!(harness::wait_until(|| sim.readdir("test_pack_state").is_ok_and(|x| x.len() == 4)));
+    sim.unlink("test_pack_state/Rat").unwrap();
+    assert_eq!(sim.read_file("test_pack_state/Shelf/First").unwrap(), "Yes\n");
+
+    // Like mounting again with the same save: the tree is gone, the module state is not.
+    with_player(sim.request.uid, || rm_dir("test_pack_state").wait()).unwrap();
+    sim.start("test_pack_state");
+    assert!(harness::wait_until(|| sim.readdir("test_pack_state").is_ok_and(|x| x.len() == 3)));
+    assert_eq!(sim.read_file("test_pack_state/Shelf/Second").unwrap(), "Good\n");
+    sim.unlink("test_pack_state/Rat").unwrap();
+    sim.unlink("test_pack_state/Rat").unwrap();
+    assert!(sim.wait_for("test_pack_state/Cheese"));
+}
+
+#[test]
+fn open_count_unlocks_with_enough_handles() {
+    let sim = install("test_open_count", r#"
+        id = "test_open_count"
+
+        [[files]]
+        path = "test_open_count"
+        name = "Gate"
+        content = "Locked.\n"
+        trigger = { kind = "open-count", opens = 2, unlocked_content = "Open!\n", then = [{ kind = "victory" }] }
+    "#);
+    assert!(sim.wait_for("test_open_count/Gate"));
+    assert_eq!(sim.read_file("test_open_count/Gate").unwrap(), "Locked.\n");
+    let first = sim.open("test_open_count/Gate", libc::O_RDONLY).unwrap();
+    assert_eq!(sim.read_file("test_open_count/Gate").unwrap(), "Open!\n");
+    sim.release(first).unwrap();
+    assert!(sim.wait_for("test_open_count/Victory"));
+}
+
+#[test]
+fn read_hooks_set_the_size_and_runaway_scripts_fail() {
+    let sim = install_with("test_hooks", r#"
+        id = "test_hooks"
+        script = "test_hooks.rhai"
+
+        [[files]]
+        path = "test_hooks"
+        name = "Sign"
+
+        [[files]]
+        path = "test_hooks"
+        name = "Trap"
+
+        [[hooks]]
+        path = "test_hooks/Sign"
+        op = "read"
+        call = "sign"
+
+        [[hooks]]
+        path = "test_hooks/Trap"
+        op = "read"
+        call = "trap"
+    "#, &[("test_hooks.rhai", r#"
+        fn sign(ctx) { "Keep out!\n" }
+        fn trap(ctx) { loop {} }
+    "#)]);
+    assert!(sim.wait_for("test_hooks/Trap"));
+    assert_eq!(sim.read_file("test_hooks/Sign").unwrap(), "Keep out!\n");
+    assert_eq!(sim.lookup("test_hooks/Sign").unwrap().size, 10);
+    assert_eq!(sim.read_file("test_hooks/Trap"), Err(IO_ERROR));
+}
+
+#[test]
+fn json_packs_load_like_toml() {
+    let dir = pack_dir("json", &[
+        ("a.json", r#"{ "id": "test_json", "files": [{ "path": "test_json", "name": "Hello", "content": "hi\n" }] }"#),
+        ("notes.txt", "not a pack"),
+    ]);
+    let packs = packs::load_dir(&dir.to_string_lossy()).unwrap();
+    assert_eq!(packs.len(), 1);
+    assert_eq!(packs[0].dir(), "test_json");
+    assert_eq!(packs[0].files[0].content, "hi\n");
+}
+
+#[test]
+fn duplicate_ids_are_rejected() {
+    let pack = r#"id = "twice""#;
+    let dir = pack_dir("duplicates", &[("a.toml", pack), ("b.toml", pack)]);
+    let error = packs::load_dir(&dir.to_string_lossy()).unwrap_err();
+    assert!(error.contains("duplicate pack id"), "{error}");
+}
+
+#[test]
+fn invalid_packs_are_rejected() {
+    let cases = [
+        (r#"id = """#, "pack id is empty"),
+        (r#"id = "x"
+            colour = "red""#, "unknown field"),
+        (r#"id = "x"
+            [[files]]
+            path = "x"
+            name = "a/b""#, "invalid file name"),
+        (r#"id = "x"
+            [[files]]
+            path = "/x"
+            name = "a""#, "relative to the mount root"),
+        (r#"id = "x"
+            [[files]]
+            path = "x"
+            name = "a"
+            trigger = { kind = "order", group = "g", position = 1 }"#, "unknown group"),
+        (r#"id = "x"
+            [[files]]
+            path = "x"
+            name = "a"
+            trigger = { kind = "order", group = "g", position = 2 }
+            [groups.g]"#, "must use positions 1 to 1"),
+        (r#"id = "x"
+            [[hooks]]
+            path = "x/a"
+            op = "read"
+            call = "f""#, "hooks need a script"),
+        (r#"id = "x"
+            [[files]]
+            path = "x"
+            name = "a"
+            trigger = { kind = "read-once", then = [{ kind = "add", file = { path = "x", name = "b", trigger = { kind = "delete-count", group = "g" } } }] }
+            [groups.g]"#, "can not join a group"),
+    ];
+    for (text, expected) in cases {
+        let error = parse_error(text);
+        assert!(error.contains(expected), "expected {expected:?}, got {error:?}");
+    }
+}
+
+const RELOAD_PACK: &str = r#"
+    id = "test_reload"
+    dir = "Test_Reload"
+
+    [[files]]
+    path = "Test_Reload"
+    name = "Teacher"
+    content = "Congradulations!\n"
+    count = 2
+
+    [[files]]
+    path = "Test_Reload"
+    name = "Trophy"
+    trigger = { kind = "read-once", then = [{ kind = "victory" }] }
+"#;
+
+#[test]
+fn reload_swaps_text_for_players_that_are_still_playing() {
+    let dir = pack_dir("test_reload", &[("test_reload.toml", RELOAD_PACK)]);
+    packs::install(packs::load_dir(&dir.to_string_lossy()).unwrap());
+    let playing = Sim::player();
+    playing.start("test_reload");
+    let done = Sim::player();
+    done.start("test_reload");
+    assert!(playing.wait_for("Test_Reload/Trophy"));
+    assert!(done.wait_for("Test_Reload/Trophy"));
+    done.read_file("Test_Reload/Trophy").unwrap();
+    assert!(done.wait_for("Test_Reload/Victory"));
+
+    fs::write(dir.join("test_reload.toml"), RELOAD_PACK.replace("Congradulations", "Congratulations")).unwrap();
+    let report = packs::reload(&dir.to_string_lossy()).unwrap();
+    assert!(report.contains("New text: Test_Reload"), "{report}");
+
+    assert_eq!(playing.readdir("Test_Reload").unwrap(), ["Teacher", "Teacher", "Trophy"]);
+    assert_eq!(playing.read_file("Test_Reload/Teacher").unwrap(), "Congratulations!\n");
+    assert_eq!(done.read_file("Test_Reload/Teacher").unwrap(), "Congradulations!\n");
+    let late = Sim::player();
+    late.start("test_reload");
+    assert!(late.wait_for("Test_Reload/Teacher"));
+    assert_eq!(late.read_file("Test_Reload/Teacher").unwrap(), "Congratulations!\n");
+}
+
+#[test]
+fn reload_leaves_everything_alone_when_a_pack_is_broken() {
+    let dir = pack_dir("test_reload_broken", &[("test_reload_broken.toml", r#"
+        id = "test_reload_broken"
+
+        [[files]]
+        path = "test_reload_broken"
+        name = "Note"
+        content = "Before\n"
+    "#)]);
+    packs::install(packs::load_dir(&dir.to_string_lossy()).unwrap());
+    let sim = Sim::player();
+    sim.start("test_reload_broken");
+    assert!(sim.wait_for("test_reload_broken/Note"));
+
+    fs::write(dir.join("test_reload_broken.toml"), "id = \"test_reload_broken\"\n[[files]]\nname = \"Note\"\ncontent = \"After\\n\"\n").unwrap();
+    assert!(packs::reload(&dir.to_string_lossy()).is_err());
+    assert_eq!(sim.read_file("test_reload_broken/Note").unwrap(), "Before\n");
+}
+
+#[test]
+fn reload_adds_new_packs() {
+    let dir = pack_dir("test_reload_new", &[]);
+    let sim = Sim::after_intro();
+    fs::write(dir.join("test_reload_new.toml"), r#"
+        id = "test_reload_new"
+        dir = "Test_Reload_New"
+
+        [[files]]
+        path = "Test_Reload_New"
+        name = "Fresh"
+    "#).unwrap();
+    let report = packs::reload(&dir.to_string_lossy()).unwrap();
+    assert!(report.contains("New: Test_Reload_New"), "{report}");
+    assert!(sim.wait_for("Test_Reload_New/Fresh"));
+}
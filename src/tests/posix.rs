@@ -0,0 +1,89 @@
+use std::{fs, os::unix::fs::{MetadataExt, PermissionsExt}};
+
+use crate::{errors::{FILE_NOT_FOUND, PERMISSION_DENIED}, harness::mount};
+
+use super::errno;
+
+#[test]
+fn hub_is_set_up_after_the_intro() {
+    let Some(mount) = mount() else { return };
+    assert!(mount.wait_gone("Welcome"));
+    let hub = mount.list("").unwrap();
+    for name in ["Welcome?", ".progress", ".control", "Submit", "Scoreboard", "The_Door", "Classroom", "Broken_Sorter", "Arena", "Library", "Vault"] {
+        assert!(hub.contains(&name.to_string()), "{name} missing from {hub:?}");
+    }
+    assert!(mount.read("Welcome?").unwrap().contains("main hub directory"));
+}
+
+#[test]
+fn user_files_need_an_underscore_in_the_hub() {
+    let Some(mount) = mount() else { return };
+    assert_eq!(errno(mount.write("notes", "hi")), Some(PERMISSION_DENIED));
+    assert_eq!(errno(fs::create_dir(mount.path("notes_dir"))), Some(PERMISSION_DENIED));
+
+    mount.write("_notes", "hi").unwrap();
+    assert_eq!(mount.read("_notes").unwrap(), "hi");
+    fs::remove_file(mount.path("_notes")).unwrap();
+    assert!(!mount.exists("_notes"));
+}
+
+#[test]
+fn files_in_user_dirs_can_be_written_and_read_back() {
+    let Some(mount) = mount() else { return };
+    fs::create_dir(mount.path("_files")).unwrap();
+    mount.write("_files/a", "hello").unwrap();
+    mount.append("_files/a", " world").unwrap();
+    assert_eq!(mount.read("_files/a").unwrap(), "hello world");
+    assert_eq!(fs::metadata(mount.path("_files/a")).unwrap().len(), 11);
+
+    mount.write("_files/a", "bye").unwrap();
+    assert_eq!(mount.read("_files/a").unwrap(), "bye");
+
+    fs::File::options().write(true).open(mount.path("_files/a")).unwrap().set_len(1).unwrap();
+    assert_eq!(mount.read("_files/a").unwrap(), "b");
+
+    let uid = unsafe { libc::getuid() };
+    assert_eq!(fs::metadata(mount.path("_files/a")).unwrap().uid(), uid);
+}
+
+#[test]
+fn directories_list_create_rename_and_remove() {
+    let Some(mount) = mount() else { return };
+    fs::create_dir(mount.path("_dirs")).unwrap();
+    fs::create_dir(mount.path("_dirs/inner")).unwrap();
+    mount.write("_dirs/b", "").unwrap();
+    mount.write("_dirs/a", "").unwrap();
+    assert_eq!(mount.list("_dirs").unwrap(), ["a", "b", "inner"]);
+
+    fs::rename(mount.path("_dirs/a"), mount.path("_dirs/inner/c")).unwrap();
+    assert_eq!(mount.list("_dirs").unwrap(), ["b", "inner"]);
+    assert_eq!(mount.list("_dirs/inner").unwrap(), ["c"]);
+
+    fs::remove_file(mount.path("_dirs/inner/c")).unwrap();
+    fs::remove_dir(mount.path("_dirs/inner")).unwrap();
+    assert_eq!(mount.list("_dirs").unwrap(), ["b"]);
+}
+
+#[test]
+fn permissions_can_be_changed_on_user_files() {
+    let Some(mount) = mount() else { return };
+    mount.write("_mode", "#!/bin/sh\n").unwrap();
+    fs::set_permissions(mount.path("_mode"), fs::Permissions::from_mode(0o755)).unwrap();
+    assert_eq!(fs::metadata(mount.path("_mode")).unwrap().permissions().mode() & 0o777, 0o755);
+}
+
+#[test]
+fn module_files_are_read_only() {
+    let Some(mount) = mount() else { return };
+    assert_eq!(errno(fs::remove_file(mount.path("Classroom/Teacher"))), Some(PERMISSION_DENIED));
+    assert_eq!(errno(fs::rename(mount.path("Classroom/Billy"), mount.path("Classroom/_Billy"))), Some(PERMISSION_DENIED));
+    assert_eq!(errno(mount.write("Classroom/Timmy", "quiet")), Some(PERMISSION_DENIED));
+    assert!(mount.exists("Classroom/Billy"));
+}
+
+#[test]
+fn missing_files_are_not_found() {
+    let Some(mount) = mount() else { return };
+    assert_eq!(errno(mount.read("No_Such_Room/file")), Some(FILE_NOT_FOUND));
+    assert_eq!(errno(fs::remove_file(mount.path("_nothing_here"))), Some(FILE_NOT_FOUND));
+}
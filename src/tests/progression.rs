@@ -0,0 +1,76 @@
+use crate::{main_fs::with_player, modules::{progression::{self, check_modules, LOCKED_FILE}, registry::{self, Module}}, packs, sim::Sim};
+
+use super::packs::pack_dir;
+
+fn module(id: &str, requires: &[&str]) -> Module {
+    Module {
+        id: id.to_string(),
+        dir: id.to_uppercase(),
+        start: (|| {}).into(),
+        hints: Vec::new(),
+        shared: false,
+        requires: requires.iter().map(|x| x.to_string()).collect(),
+    }
+}
+
+#[test]
+fn solving_a_module_unlocks_the_next() {
+    let dir = pack_dir("test_chain", &[
+        ("first.toml", r#"
+            id = "test_first"
+            dir = "Test_First"
+
+            [[files]]
+            path = "Test_First"
+            name = "Trophy"
+            trigger = { kind = "read-once", then = [{ kind = "victory" }] }
+        "#),
+        ("second.toml", r#"
+            id = "test_second"
+            dir = "Test_Second"
+            requires = ["test_first"]
+
+            [[files]]
+            path = "Test_Second"
+            name = "Prize"
+        "#),
+    ]);
+    packs::install(packs::load_dir(&dir.to_string_lossy()).unwrap());
+
+    let sim = Sim::player();
+    assert!(sim.exists("Welcome"));
+    with_player(sim.request.uid, || {
+        progression::show(&registry::get("test_first").unwrap());
+        progression::show(&registry::get("test_second").unwrap());
+    });
+
+    let locked = format!("Test_Second/{LOCKED_FILE}");
+    assert!(sim.wait_for(&locked));
+    assert_eq!(sim.readdir("Test_Second").unwrap(), [LOCKED_FILE]);
+    assert_eq!(sim.read_file(&locked).unwrap(), "This room is locked.\nSolve Test_First first.\n");
+
+    sim.read_file("Test_First/Trophy").unwrap();
+    assert!(sim.wait_for("Test_Second/Prize"));
+    assert!(!sim.exists(&locked));
+}
+
+#[test]
+fn requirements_are_checked() {
+    assert!(check_modules(&[module("a", &[]), module("b", &["a"]), module("c", &["A", "b"])]).is_ok());
+    assert!(check_modules(&[module("a", &["nowhere"])]).unwrap_err().contains("not a selected module"));
+    assert!(check_modules(&[module("a", &["a"])]).unwrap_err().contains("itself"));
+    let cycle = check_modules(&[module("a", &["c"]), module("b", &["a"]), module("c", &["b"])]).unwrap_err();
+    assert!(cycle.contains("A -> C -> B -> A"), "{cycle}");
+}
+
+#[test]
+fn every_module_leaves_out_other_final_rooms() {
+    assert!(check_modules(&[module("a", &[]), module("end", &["*"]), module("other_end", &["*"])]).is_ok());
+    assert!(check_modules(&[module("a", &["end"]), module("end", &["*"])]).unwrap_err().contains("require each other"));
+}
+
+#[test]
+fn bad_settings_are_rejected() {
+    assert!(progression::set_requirements(&["nowhere=Classroom".to_string()]).is_err());
+    assert!(progression::set_requirements(&["Classroom".to_string()]).is_err());
+}
@@ -0,0 +1,30 @@
+use std::fs;
+
+use rand::Rng;
+
+use crate::{harness, main_fs::with_player, modules::correct_order, random, sim::Sim};
+
+#[test]
+fn players_get_their_own_parameters() {
+    let players: Vec<Sim> = (0..8).map(|_| Sim::player()).collect();
+    let orders: Vec<Vec<usize>> = players.iter().map(|sim| with_player(sim.request.uid, correct_order::order)).collect();
+    assert!(orders.iter().any(|order| *order != orders[0]), "every player got the order {:?}", orders[0]);
+
+    // The same player and module always draw the same numbers.
+    for (sim, order) in players.iter().zip(&orders) {
+        assert_eq!(with_player(sim.request.uid, correct_order::order), *order);
+    }
+    let draw = |module: &str| with_player(players[0].request.uid, || random::rng(module).gen::<u64>());
+    assert_eq!(draw("a"), draw("a"));
+    assert_ne!(draw("a"), draw("b"));
+}
+
+#[test]
+fn the_seed_is_saved() {
+    let sim = Sim::player();
+    sim.start(correct_order::ID);
+    let path = harness::dir().join("state/save.json");
+    assert!(harness::wait_until(|| path.exists()));
+    let save: serde_json::Value = serde_json::from_slice(&fs::read(path).unwrap()).unwrap();
+    assert_eq!(save["seed"], random::seed());
+}
@@ -0,0 +1,28 @@
+use crate::{main_fs::UID, progress::Intro, save::{parse, SAVE_VERSION}};
+
+#[test]
+fn single_player_saves_go_to_the_owner() {
+    let save = parse(br#"{
+        "progress": { "intro": "done", "modules": { "many_open": { "started": 1, "solved": 2 } } },
+        "state": { "kill_them_all": { "ogres_left": 3 } }
+    }"#).unwrap();
+    assert_eq!(save.version, SAVE_VERSION);
+    assert_eq!(save.progress[&*UID].intro, Intro::Done);
+    assert_eq!(save.progress[&*UID].modules["many_open"].solved, Some(2));
+    assert_eq!(save.state[&*UID]["kill_them_all"]["ogres_left"], 3);
+}
+
+#[test]
+fn saves_keyed_by_player_load_with_or_without_a_version() {
+    let text = r#"{ "progress": { "1000": { "intro": "hub", "modules": {} } }, "state": {}, "seed": 7 }"#;
+    let save = parse(text.as_bytes()).unwrap();
+    assert_eq!(save.progress[&1000].intro, Intro::Hub);
+    assert_eq!(save.seed, Some(7));
+    let versioned = text.replacen('{', r#"{ "version": 2,"#, 1);
+    assert_eq!(parse(versioned.as_bytes()).unwrap().progress[&1000].intro, Intro::Hub);
+}
+
+#[test]
+fn newer_saves_are_refused() {
+    assert!(parse(br#"{ "version": 99, "progress": {}, "state": {} }"#).unwrap_err().contains("newer"));
+}
@@ -0,0 +1,121 @@
+use std::{sync::{atomic::{AtomicU32, Ordering}, Arc}, time::Duration};
+
+use crate::{background_tasks::add_file, errors::PERMISSION_DENIED, file_helpers::text_file, main_fs::with_player, module_state, modules::registry, scheduler::{after, cancel, every}, sim::Sim};
+
+use super::packs::install_with;
+
+const SECOND: Duration = Duration::from_secs(1);
+
+fn paused_player() -> Sim {
+    let sim = Sim::player();
+    assert!(sim.exists("Welcome"));
+    sim.pause_clock();
+    sim
+}
+
+#[test]
+fn one_shot_jobs_run_when_due() {
+    let sim = paused_player();
+    with_player(sim.request.uid, || {
+        after("test", 2 * SECOND, || {
+            add_file("_later", text_file("second", ""));
+        });
+        after("test", SECOND, || {
+            add_file("_later", text_file("first", ""));
+        });
+    });
+    sim.advance(SECOND / 2);
+    assert!(!sim.exists("_later"));
+    sim.advance(SECOND);
+    assert_eq!(sim.readdir("_later").unwrap(), ["first"]);
+    sim.advance(SECOND);
+    assert_eq!(sim.readdir("_later").unwrap(), ["first", "second"]);
+}
+
+#[test]
+fn periodic_jobs_run_until_cancelled() {
+    let sim = paused_player();
+    let runs = Arc::new(AtomicU32::new(0));
+    let counter = runs.clone();
+    let job = with_player(sim.request.uid, || every("test", SECOND, move || {
+        counter.fetch_add(1, Ordering::SeqCst);
+    }));
+    sim.advance(3 * SECOND);
+    assert_eq!(runs.load(Ordering::SeqCst), 3);
+    assert!(cancel(job));
+    assert!(!cancel(job));
+    sim.advance(3 * SECOND);
+    assert_eq!(runs.load(Ordering::SeqCst), 3);
+}
+
+#[test]
+fn other_players_keep_real_time() {
+    let paused = paused_player();
+    let running = Sim::player();
+    assert!(running.exists("Welcome"));
+    with_player(paused.request.uid, || {
+        after("test", Duration::ZERO, || {
+            add_file("_now", text_file("a", ""));
+        });
+    });
+    with_player(running.request.uid, || {
+        after("test", Duration::ZERO, || {
+            add_file("_now", text_file("a", ""));
+        });
+    });
+    assert!(running.wait_for("_now/a"));
+    assert!(!paused.exists("_now"));
+    paused.advance(Duration::ZERO);
+    assert!(paused.exists("_now/a"));
+}
+
+#[test]
+fn vault_unjams_after_two_seconds() {
+    let sim = Sim::after_intro();
+    sim.pause_clock();
+    sim.write_file("Vault/Lock", "Open sesame\n").unwrap();
+    sim.advance(SECOND);
+    assert_eq!(sim.write_file("Vault/Lock", "Welcome\n"), Err(PERMISSION_DENIED));
+    sim.advance(SECOND);
+    sim.write_file("Vault/Lock", "Welcome\n").unwrap();
+    assert!(sim.read_file("Vault/Lock").unwrap().contains("The lock is open"));
+}
+
+#[test]
+fn reset_cancels_the_module_jobs() {
+    let sim = install_with("test_ticks", r#"
+        id = "test_ticks"
+        dir = "Test_Ticks"
+        script = "test_ticks.rhai"
+
+        [[files]]
+        path = "Test_Ticks"
+        name = "Clock"
+
+        [[hooks]]
+        path = "Test_Ticks/Clock"
+        op = "read"
+        call = "read_clock"
+    "#, &[("test_ticks.rhai", r#"
+        fn read_clock(ctx) {
+            if state_get("started") != true {
+                state_set("started", true);
+                every(1000, "tick");
+            }
+            `${state_get("ticks") ?? 0}`
+        }
+
+        fn tick(ctx) {
+            state_set("ticks", (state_get("ticks") ?? 0) + 1);
+        }
+    "#)]);
+    sim.pause_clock();
+    assert!(sim.wait_for("Test_Ticks/Clock"));
+    assert_eq!(sim.read_file("Test_Ticks/Clock").unwrap(), "0");
+    sim.advance(2 * SECOND);
+    assert_eq!(sim.read_file("Test_Ticks/Clock").unwrap(), "2");
+
+    with_player(sim.request.uid, || registry::reset("test_ticks")).unwrap();
+    sim.advance(2 * SECOND);
+    assert_eq!(with_player(sim.request.uid, || module_state::get("test_ticks", "ticks")), None);
+}
@@ -0,0 +1,8 @@
+use crate::scoreboard::serve;
+
+#[test]
+fn only_loopback_is_served_without_opting_in() {
+    assert!(serve("0.0.0.0:0", false).unwrap_err().contains("--scoreboard-public"));
+    assert!(serve("127.0.0.1:0", false).is_ok());
+    assert!(serve("0", false).is_ok());
+}
@@ -0,0 +1,18 @@
+use crate::{cli, harness, shutdown};
+
+#[test]
+fn summary_counts_operations_and_players() {
+    for _ in 0..3 {
+        shutdown::count("test_summary", 4_000_000);
+    }
+    let summary = shutdown::summary();
+    assert!(summary.iter().any(|line| line.contains("operations") && line.contains("test_summary 3")), "{summary:?}");
+    assert!(summary.iter().any(|line| line.contains("players seen") && line.contains("4000000")), "{summary:?}");
+    assert!(summary.iter().any(|line| line.starts_with("solved: ") && line.contains("The_Door by ")), "{summary:?}");
+}
+
+#[test]
+fn only_disconnected_mounts_are_stale() {
+    assert!(!cli::is_stale(harness::dir()));
+    assert!(!cli::is_stale(&harness::dir().join("nothing here")));
+}
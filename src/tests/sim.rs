@@ -0,0 +1,131 @@
+use crate::{errors::{FILE_NOT_FOUND, PERMISSION_DENIED}, flags, main_fs::with_player, modules::{classroom, correct_order, kill_them_all, many_open}, scoreboard::SUBMIT_FILE, sim::Sim};
+
+/// Waits for the module's `Victory` file and checks that it holds this player's flag.
+fn assert_victory(sim: &Sim, dir: &str, id: &str) -> String {
+    let victory = format!("{dir}/Victory");
+    assert!(sim.wait_for(&victory), "{victory} never showed up");
+    let text = sim.read_file(&victory).unwrap();
+    let flag = text.split_whitespace().find(|x| x.starts_with("FLAG{")).expect("no flag in the Victory file").to_string();
+    assert_eq!(flags::verify(&flag), Ok((id.to_string(), sim.request.uid)));
+    flag
+}
+
+#[test]
+fn intro_leads_to_the_hub() {
+    let sim = Sim::player();
+    assert!(sim.wait_for("Welcome"));
+    assert!(!sim.exists("Welcome?"));
+    assert!(!sim.exists("Classroom"));
+    assert!(sim.read_file("Welcome").unwrap().contains("welcome to the linux challenge"));
+    assert!(sim.wait_for("Welcome?"));
+    sim.read_file("Welcome?").unwrap();
+    assert!(sim.wait_for("Classroom"));
+}
+
+#[test]
+fn door_counts_open_handles() {
+    let sim = Sim::after_intro();
+    let needed = with_player(sim.request.uid, many_open::opens_needed) as usize;
+    let path = format!("{}/Heavy_Door", many_open::BASE_PATH);
+    let handles: Vec<_> = (1..needed).map(|_| sim.open(&path, libc::O_RDONLY).unwrap()).collect();
+    let text = sim.read(&handles[0], 0, 4096).unwrap();
+    assert!(String::from_utf8(text).unwrap().contains(&format!("You would need {needed} men")));
+
+    let last = sim.open(&path, libc::O_RDONLY).unwrap();
+    assert!(sim.read(&last, 0, 4096).map(|x| String::from_utf8(x).unwrap()).unwrap().contains("Wow"));
+    for handle in handles.into_iter().chain([last]) {
+        sim.release(handle).unwrap();
+    }
+    assert_victory(&sim, many_open::BASE_PATH, many_open::ID);
+}
+
+#[test]
+fn sally_needs_read_write() {
+    let sim = Sim::after_intro();
+    assert_eq!(sim.read_file("Classroom/Sally"), Err(FILE_NOT_FOUND));
+    assert!(sim.read_with("Classroom/Sally", libc::O_RDWR).unwrap().contains("YEAYYY"));
+    assert_victory(&sim, classroom::BASE_PATH, classroom::ID);
+}
+
+#[test]
+fn sorter_starts_over_on_a_wrong_read() {
+    let sim = Sim::after_intro();
+    let order = with_player(sim.request.uid, correct_order::order);
+    let read = |file: usize| sim.read_file(&format!("Broken_Sorter/{file}")).unwrap();
+    assert_eq!(read(order[0]), "Yes\n");
+    assert_eq!(read(order[1]), "Yes\n");
+    assert_eq!(read(order[0]), "No \n");
+    for file in &order {
+        assert_eq!(read(*file), "Yes\n");
+    }
+    assert_victory(&sim, correct_order::BASE_PATH, correct_order::ID);
+}
+
+#[test]
+fn arena_needs_every_ogre_dead() {
+    let sim = Sim::after_intro();
+    let mut killed = 0;
+    while sim.unlink("Arena/Ogre").is_ok() {
+        killed += 1;
+    }
+    assert_eq!(killed, with_player(sim.request.uid, kill_them_all::num_ogres));
+    assert_eq!(sim.readdir("Arena").unwrap().iter().filter(|x| *x == "Ogre").count(), 0);
+    assert_victory(&sim, kill_them_all::BASE_PATH, kill_them_all::ID);
+}
+
+#[test]
+fn vault_jams_on_a_wrong_password() {
+    let sim = Sim::after_intro();
+    sim.write_file("Vault/Lock", "Open sesame\n").unwrap();
+    assert!(sim.read_file("Vault/Lock").unwrap().contains("1 wrong passwords"));
+    // Jammed for two seconds, so even the right password is turned away.
+    assert_eq!(sim.write_file("Vault/Lock", "Welcome\n"), Err(PERMISSION_DENIED));
+    assert!(sim.read_file("Vault/Lock").unwrap().contains("The lock is closed"));
+    assert!(!sim.exists("Vault/Victory"));
+}
+
+#[test]
+fn players_only_see_their_own_progress() {
+    let winner = Sim::after_intro();
+    let other = Sim::after_intro();
+    assert!(winner.read_with("Classroom/Sally", libc::O_RDWR).is_ok());
+    let flag = assert_victory(&winner, classroom::BASE_PATH, classroom::ID);
+    assert!(!other.exists("Classroom/Victory"));
+
+    other.write_file(SUBMIT_FILE, &format!("{flag}\n")).unwrap();
+    assert!(other.read_file(SUBMIT_FILE).unwrap().contains("belongs to someone else"));
+    winner.write_file(SUBMIT_FILE, &format!("{flag}\n")).unwrap();
+    assert!(winner.read_file(SUBMIT_FILE).unwrap().contains("Accepted Classroom"));
+}
+
+#[test]
+fn user_dirs_behave_like_directories() {
+    let sim = Sim::after_intro();
+    assert_eq!(sim.create("notes", 0o644).err(), Some(PERMISSION_DENIED));
+    sim.mkdir("_work", 0o755).unwrap();
+    sim.write_file("_work/a", "one").unwrap();
+    sim.write_file("_work/b", "two").unwrap();
+    assert_eq!(sim.readdir("_work").unwrap(), ["a", "b"]);
+
+    sim.rename("_work/a", "_work/c").unwrap();
+    assert_eq!(sim.read_file("_work/c").unwrap(), "one");
+    sim.chmod("_work/c", 0o700).unwrap();
+    assert_eq!(sim.lookup("_work/c").unwrap().perm & 0o777, 0o700);
+    assert_eq!(sim.lookup("_work/c").unwrap().uid, sim.request.uid);
+
+    sim.unlink("_work/b").unwrap();
+    assert_eq!(sim.read_file("_work/b"), Err(FILE_NOT_FOUND));
+    assert_eq!(sim.readdir("_work").unwrap(), ["c"]);
+
+    sim.mkdir("_work/inner", 0o755).unwrap();
+    sim.rmdir("_work/inner").unwrap();
+    assert!(!sim.exists("_work/inner"));
+}
+
+#[test]
+fn module_files_can_not_be_changed() {
+    let sim = Sim::after_intro();
+    assert_eq!(sim.unlink("Classroom/Teacher"), Err(PERMISSION_DENIED));
+    assert_eq!(sim.rename("Classroom/Billy", "_Billy"), Err(PERMISSION_DENIED));
+    assert_eq!(sim.write_file("Classroom/Timmy", "quiet"), Err(PERMISSION_DENIED));
+}
@@ -0,0 +1,86 @@
+use crate::{background_tasks::{add_dir, add_file, batch, flush, rm_dir, rm_file}, file_helpers::text_file, main_fs::with_player, ops, sim::Sim};
+
+/// A fresh player whose tree is already built.
+fn player() -> Sim {
+    let sim = Sim::player();
+    assert!(sim.exists("Welcome"));
+    sim
+}
+
+#[test]
+fn changes_are_applied_in_order() {
+    let sim = player();
+    with_player(sim.request.uid, || {
+        add_file("_tasks", text_file("a", "one"));
+        rm_file("_tasks/a");
+        add_file("_tasks", text_file("b", "two"));
+    });
+    flush();
+    assert_eq!(sim.readdir("_tasks").unwrap(), ["b"]);
+    assert_eq!(sim.read_file("_tasks/b").unwrap(), "two");
+}
+
+#[test]
+fn a_failed_batch_changes_nothing() {
+    let sim = player();
+    let result = with_player(sim.request.uid, || batch(|| {
+        add_file("_batch/inner", text_file("a", "one"));
+        rm_file("Welcome");
+        rm_file("_batch/missing");
+    })).wait();
+    assert!(result.is_err());
+    assert!(!sim.exists("_batch"));
+    assert!(sim.exists("Welcome"));
+}
+
+#[test]
+fn a_rollback_keeps_the_order_of_a_directory() {
+    let sim = player();
+    with_player(sim.request.uid, || {
+        add_file("_order", text_file("a", "one"));
+        add_file("_order", text_file("b", "two"));
+        add_file("_order", text_file("c", "three"));
+    });
+    flush();
+    let names = || {
+        let ino = sim.lookup("_order").unwrap().ino;
+        ops::readdir(ino, 0).unwrap().into_iter().map(|(_, _, _, name)| name.into_string().unwrap()).collect::<Vec<_>>()
+    };
+    assert_eq!(names(), ["a", "b", "c"]);
+
+    let result = with_player(sim.request.uid, || batch(|| {
+        rm_file("_order/a");
+        rm_file("_order/missing");
+    })).wait();
+    assert!(result.is_err());
+    assert_eq!(names(), ["a", "b", "c"]);
+}
+
+#[test]
+fn nested_batches_are_one_task() {
+    let sim = player();
+    let (outer, inner) = with_player(sim.request.uid, || {
+        let mut inner = None;
+        let outer = batch(|| {
+            add_dir("_nested", 0o755);
+            inner = Some(batch(|| {
+                rm_file("_nested/missing");
+            }));
+        });
+        (outer, inner.unwrap())
+    });
+    assert!(outer.wait().is_err());
+    assert!(inner.wait().is_err());
+    assert!(!sim.exists("_nested"));
+}
+
+#[test]
+fn removing_what_is_not_there_is_an_error() {
+    let sim = player();
+    with_player(sim.request.uid, || {
+        assert!(rm_file("_nothing/here").wait().is_err());
+        assert!(add_file("Welcome/inside", text_file("a", "one")).wait().is_err());
+        assert!(rm_dir("_nothing").wait().is_ok());
+    });
+    assert!(sim.exists("Welcome"));
+}
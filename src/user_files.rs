@@ -205,6 +205,12 @@ impl Dir for NormalDir {
         self.children.retain(|x| *x != ino);
         return Ok(())
     }
+
+    fn insert_child(&mut self, index: usize, ino: u64) -> Result<(), c_int> {
+        self.children.insert(index.min(self.children.len()), ino);
+        self.attr.size = self.children.len() as u64;
+        Ok(())
+    }
     
     fn rename(&mut self, new_name: &OsStr, in_user_dir: bool) -> Result<(), c_int> {
         if !in_user_dir && new_name.as_bytes()[0] != b'_' {
@@ -220,7 +226,6 @@ impl Dir for NormalDir {
     }
     
     fn get_child(&self, index: usize) -> Option<u64> {
-        println!("Index: {index}, len:{}, {:?}", self.children.len(), self.children.get(index).copied());
         self.children.get(index).copied()
     }
     
@@ -0,0 +1,19 @@
+use std::process::Command;
+
+#[test]
+fn reference_solutions_beat_their_modules() {
+    let output = Command::new(env!("CARGO_BIN_EXE_hack"))
+        .arg("check-solutions")
+        .arg(concat!(env!("CARGO_MANIFEST_DIR"), "/solutions"))
+        .output()
+        .expect("Failed to run hack");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    // Same as the mount tests in the crate: skip without FUSE, unless it is required.
+    let required = std::env::var_os("HACK_REQUIRE_FUSE").is_some_and(|x| !x.is_empty() && x != "0");
+    if stderr.contains("could not mount the challenge") && !required {
+        eprintln!("skipping, could not mount the challenge");
+        return;
+    }
+    assert!(output.status.success(), "{stdout}\n{stderr}");
+}